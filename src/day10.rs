@@ -1,13 +1,19 @@
-use std::{
-    cmp::max,
-    collections::HashMap,
-    fs, thread,
-};
+use std::{collections::HashMap, env, fs};
 
-const GRID_MAX: u64 = 139;
+//rows and columns of the grid
+type Size = (u64, u64);
 
-fn is_cell_exist(cell: &(u64, u64)) -> bool {
-    max(GRID_MAX, max(cell.0, cell.1)) <= GRID_MAX
+fn is_cell_exist(cell: &(u64, u64), size: Size) -> bool {
+    cell.0 < size.0 && cell.1 < size.1
+}
+
+fn get_size(lines: &str) -> Size {
+    let rows = lines
+        .split('\n')
+        .filter(|l| l.len() >= 2)
+        .collect::<Vec<_>>();
+    let cols = rows.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    (rows.len() as u64, cols as u64)
 }
 
 fn add_connection_if_exist(
@@ -15,13 +21,14 @@ fn add_connection_if_exist(
     col: u64,
     target_row: Option<u64>,
     target_col: Option<u64>,
+    size: Size,
     map: &mut HashMap<(u64, u64), Vec<(u64, u64)>>,
 ) {
     map.entry((row, col)).or_insert(Vec::new());
 
     if target_row.is_some() && target_col.is_some() {
         let target = (target_row.unwrap(), target_col.unwrap());
-        if is_cell_exist(&target) {
+        if is_cell_exist(&target, size) {
             map.entry((row, col)).and_modify(|a| a.push(target));
         }
     }
@@ -31,6 +38,7 @@ fn add_connections(
     row: u64,
     col: u64,
     symbol: char,
+    size: Size,
     map: &mut HashMap<(u64, u64), Vec<(u64, u64)>>,
 ) {
     let cur_row = Some(row);
@@ -42,28 +50,28 @@ fn add_connections(
 
     match symbol {
         '|' => {
-            add_connection_if_exist(row, col, up_row, cur_col, map);
-            add_connection_if_exist(row, col, down_row, cur_col, map);
+            add_connection_if_exist(row, col, up_row, cur_col, size, map);
+            add_connection_if_exist(row, col, down_row, cur_col, size, map);
         }
         '-' => {
-            add_connection_if_exist(row, col, cur_row, left_col, map);
-            add_connection_if_exist(row, col, cur_row, right_col, map);
+            add_connection_if_exist(row, col, cur_row, left_col, size, map);
+            add_connection_if_exist(row, col, cur_row, right_col, size, map);
         }
         'L' => {
-            add_connection_if_exist(row, col, up_row, cur_col, map);
-            add_connection_if_exist(row, col, cur_row, right_col, map);
+            add_connection_if_exist(row, col, up_row, cur_col, size, map);
+            add_connection_if_exist(row, col, cur_row, right_col, size, map);
         }
         'J' => {
-            add_connection_if_exist(row, col, up_row, cur_col, map);
-            add_connection_if_exist(row, col, cur_row, left_col, map);
+            add_connection_if_exist(row, col, up_row, cur_col, size, map);
+            add_connection_if_exist(row, col, cur_row, left_col, size, map);
         }
         '7' => {
-            add_connection_if_exist(row, col, down_row, cur_col, map);
-            add_connection_if_exist(row, col, cur_row, left_col, map);
+            add_connection_if_exist(row, col, down_row, cur_col, size, map);
+            add_connection_if_exist(row, col, cur_row, left_col, size, map);
         }
         'F' => {
-            add_connection_if_exist(row, col, down_row, cur_col, map);
-            add_connection_if_exist(row, col, cur_row, right_col, map);
+            add_connection_if_exist(row, col, down_row, cur_col, size, map);
+            add_connection_if_exist(row, col, cur_row, right_col, size, map);
         }
         _ => {
             // no connection but still add mappings?
@@ -72,23 +80,45 @@ fn add_connections(
     }
 }
 
-fn find_s_mappings(s: Option<(u64, u64)>, map: &mut HashMap<(u64, u64), Vec<(u64, u64)>>) {
-    let mut neighbours = Vec::new();
-    if s.is_some() {
-        for k in map.keys() {
-            if map.get(k).unwrap().contains(&s.unwrap()) {
-                neighbours.push(*k);
-            }
+//works out which pipe S is from the neighbours pointing at it, then connects S like that pipe
+fn find_s_mappings(
+    s: Option<(u64, u64)>,
+    size: Size,
+    map: &mut HashMap<(u64, u64), Vec<(u64, u64)>>,
+) -> Result<char, String> {
+    let s = s.ok_or("No S in the maze")?;
+    let points_at_s = |cell: Option<(u64, u64)>| {
+        cell.and_then(|c| map.get(&c))
+            .is_some_and(|m| m.contains(&s))
+    };
+    let up = points_at_s(s.0.checked_sub(1).map(|r| (r, s.1)));
+    let down = points_at_s(Some((s.0 + 1, s.1)));
+    let left = points_at_s(s.1.checked_sub(1).map(|c| (s.0, c)));
+    let right = points_at_s(Some((s.0, s.1 + 1)));
+
+    let shape = match (up, down, left, right) {
+        (true, true, false, false) => '|',
+        (false, false, true, true) => '-',
+        (true, false, false, true) => 'L',
+        (true, false, true, false) => 'J',
+        (false, true, true, false) => '7',
+        (false, true, false, true) => 'F',
+        _ => {
+            return Err(format!(
+                "S at {:?} is ambiguous: up {}, down {}, left {}, right {}",
+                s, up, down, left, right
+            ))
         }
+    };
 
-        map.entry(s.unwrap())
-            .and_modify(|m| m.append(&mut neighbours));
-    }
+    map.insert(s, Vec::new());
+    add_connections(s.0, s.1, shape, size, map);
+    Ok(shape)
 }
 
-fn load_map(file: &str, map: &mut HashMap<(u64, u64), Vec<(u64, u64)>>) -> Option<(u64, u64)> {
+fn parse_map(lines: &str, map: &mut HashMap<(u64, u64), Vec<(u64, u64)>>) -> Option<(u64, u64)> {
     let mut s_loc: Option<(u64, u64)> = None;
-    let lines = fs::read_to_string(file).expect("file not found");
+    let size = get_size(lines);
 
     let split = lines.split('\n').collect::<Vec<_>>();
 
@@ -99,7 +129,7 @@ fn load_map(file: &str, map: &mut HashMap<(u64, u64), Vec<(u64, u64)>>) -> Optio
         }
         let mut col = 0;
         for c in line.chars().collect::<Vec<_>>() {
-            add_connections(row, col, c, map);
+            add_connections(row, col, c, size, map);
             if c == 'S' {
                 s_loc = Some((row, col));
             }
@@ -177,221 +207,301 @@ fn traverse_from_s(
 3. traverse from S to all reachable cells?
 4. find max
 */
-fn p1(file: &str) -> u64 {
+fn p1(lines: &str) -> u64 {
     let mut map: HashMap<(u64, u64), Vec<(u64, u64)>> = HashMap::new();
-    let s_loc = load_map(file, &mut map);
-    find_s_mappings(s_loc, &mut map);
+    let s_loc = parse_map(lines, &mut map);
+    find_s_mappings(s_loc, get_size(lines), &mut map).expect("Cannot place S");
     let mut dist_from_s: HashMap<(u64, u64), u64> = HashMap::new();
 
     traverse_from_s(s_loc.unwrap(), &map, &mut dist_from_s);
     *dist_from_s.values().max().unwrap()
 }
 
-fn write_loop(file: &str, p2_file: &str, points: Vec<(u64, u64)>) {
-    let lines = fs::read_to_string(file).expect("file not found");
-    let mut output = String::new();
+//the grid at double resolution, with the loop drawn as '#' including the gaps
+//between its tiles, so the outside can be filled around it
+fn expand_loop(lines: &str, points: Vec<(u64, u64)>) -> Vec<Vec<char>> {
+    let size = get_size(lines);
+    let mut expanded = vec![vec![' '; (size.1 * 2 + 2) as usize]; (size.0 * 2 + 2) as usize];
 
     let split = lines.split('\n').collect::<Vec<_>>();
 
-    let mut expanded_map: HashMap<(u64, u64), char> = HashMap::new();
-
     let mut row = 0;
     for line in split {
         if line.len() < 2 {
             continue;
         }
-        let mut col = 0;
-        for c in line.chars().collect::<Vec<_>>() {
-            let mapped_row = row * 2 + 1;
-            let mapped_col = col * 2 + 1;
-
-            expanded_map.insert((mapped_row, mapped_col), c);
-
-            col += 1;
+        for (col, c) in line.chars().enumerate() {
+            expanded[row * 2 + 1][col * 2 + 1] = c;
         }
         row += 1;
     }
 
     for p in points {
-        expanded_map.insert(p, '#');
-    }
-
-    for r in 0..GRID_MAX * 2 + 2 {
-        for c in 0..GRID_MAX * 2 + 2 {
-            if expanded_map.contains_key(&(r, c)) {
-                output.push(*expanded_map.get(&(r, c)).unwrap());
-            } else {
-                output.push(' ');
-            }
-        }
-        output.push('\n');
+        expanded[p.0 as usize][p.1 as usize] = '#';
     }
-
-    fs::write(p2_file, output).expect("Cannot write p2");
+    expanded
 }
 
-fn flood_fill_cell(cell: (u64, u64), map: &mut HashMap<(u64, u64), char>) {
-    if map.contains_key(&cell) {
-        let cur = *map.get(&cell).unwrap();
+fn flood_fill_cell(cell: (usize, usize), map: &mut [Vec<char>]) {
+    //explicit stack, the fill can cover millions of cells
+    let mut to_fill = vec![cell];
+    while let Some((row, col)) = to_fill.pop() {
+        let cur = map[row][col];
         if cur.eq(&'#') || cur.eq(&'A') {
-            return;
+            continue;
         }
-    }
-
-    map.insert(cell, '#');
-    let up = cell.0.checked_sub(1);
-    let down = if cell.0 >= GRID_MAX * 2 + 2 {
-        None
-    } else {
-        Some(cell.0 + 1)
-    };
-    let left = cell.1.checked_sub(1);
-    let right = if cell.1 >= GRID_MAX * 2 + 2 {
-        None
-    } else {
-        Some(cell.1 + 1)
-    };
 
-    if up.is_some() {
-        flood_fill_cell((up.unwrap(), cell.1), map);
-    }
-    if left.is_some() {
-        flood_fill_cell((cell.0, left.unwrap()), map);
-    }
-    if right.is_some() {
-        flood_fill_cell((cell.0, right.unwrap()), map);
-    }
-    if down.is_some() {
-        flood_fill_cell((down.unwrap(), cell.1), map);
+        map[row][col] = '#';
+        if row > 0 {
+            to_fill.push((row - 1, col));
+        }
+        if col > 0 {
+            to_fill.push((row, col - 1));
+        }
+        if col + 1 < map[row].len() {
+            to_fill.push((row, col + 1));
+        }
+        if row + 1 < map.len() {
+            to_fill.push((row + 1, col));
+        }
     }
 }
 
-fn flood_fill(file: &str) -> u64 {
-    let lines = fs::read_to_string(file).expect("file not found");
+fn flood_fill(expanded_map: &mut [Vec<char>]) -> u64 {
+    flood_fill_cell((0, 0), expanded_map);
+    expanded_map
+        .iter()
+        .flatten()
+        .filter(|c| **c != '#' && **c != ' ')
+        .count() as u64
+}
 
-    let split = lines.split('\n').collect::<Vec<_>>();
+fn p2_flood_fill(lines: &str) -> u64 {
+    let mut map: HashMap<(u64, u64), Vec<(u64, u64)>> = HashMap::new();
+    let s_loc = parse_map(lines, &mut map);
+    find_s_mappings(s_loc, get_size(lines), &mut map).expect("Cannot place S");
+    let mut dist_from_s: HashMap<(u64, u64), u64> = HashMap::new();
 
-    let mut expanded_map: HashMap<(u64, u64), char> = HashMap::new();
+    let points = traverse_from_s(s_loc.unwrap(), &map, &mut dist_from_s);
 
-    let mut row = 0;
-    for line in split {
-        if line.len() < 2 {
-            continue;
-        }
-        let mut col = 0;
-        for c in line.chars().collect::<Vec<_>>() {
-            expanded_map.insert((row, col), c);
+    let mut expanded = expand_loop(lines, points);
+    flood_fill(&mut expanded)
+}
 
-            col += 1;
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Tile {
+    Loop(u64), //distance from S
+    Inside,
+    Outside,
+}
+
+//scans each row, a tile is inside after an odd number of loop pipes going up
+//('|', 'L', 'J'), which counts a F-J or L-7 run once and F-7 or L-J twice
+fn classify_tiles(
+    lines: &str,
+    dist_from_s: &HashMap<(u64, u64), u64>,
+    s: (u64, u64),
+    s_shape: char,
+) -> Vec<Vec<Tile>> {
+    let mut result = Vec::new();
+    let rows = lines.split('\n').filter(|l| l.len() >= 2);
+    for (row, line) in rows.enumerate() {
+        let mut inside = false;
+        let mut tiles = Vec::new();
+        for (col, c) in line.chars().enumerate() {
+            let cell = (row as u64, col as u64);
+            let dist = if cell == s {
+                Some(0)
+            } else {
+                dist_from_s.get(&cell).copied()
+            };
+            if let Some(d) = dist {
+                let c = if cell == s { s_shape } else { c };
+                if matches!(c, '|' | 'L' | 'J') {
+                    inside = !inside;
+                }
+                tiles.push(Tile::Loop(d));
+            } else if inside {
+                tiles.push(Tile::Inside);
+            } else {
+                tiles.push(Tile::Outside);
+            }
         }
-        row += 1;
+        result.push(tiles);
     }
+    result
+}
 
-    flood_fill_cell((0, 0), &mut expanded_map);
+fn count_enclosed(tiles: &[Vec<Tile>]) -> u64 {
+    tiles
+        .iter()
+        .flatten()
+        .filter(|t| **t == Tile::Inside)
+        .count() as u64
+}
+
+fn get_glyph(c: char) -> char {
+    match c {
+        '|' => '│',
+        '-' => '─',
+        'L' => '└',
+        'J' => '┘',
+        '7' => '┐',
+        'F' => '┌',
+        _ => c,
+    }
+}
+
+//loop pipes in box drawing, other tiles as I or O, or with colour: the loop in bold
+//or shaded blue to red by distance from S, and junk pipes dimmed, green when inside
+fn render(
+    lines: &str,
+    tiles: &[Vec<Tile>],
+    s_shape: char,
+    colour: bool,
+    distances: bool,
+) -> String {
+    let max_dist = tiles
+        .iter()
+        .flatten()
+        .filter_map(|t| match t {
+            Tile::Loop(d) => Some(*d),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0);
 
     let mut output = String::new();
-    for r in 0..GRID_MAX * 2 + 2 {
-        for c in 0..GRID_MAX * 2 + 2 {
-            if expanded_map.contains_key(&(r, c)) {
-                output.push(*expanded_map.get(&(r, c)).unwrap());
-            } else {
-                output.push(' ');
-            }
+    let rows = lines.split('\n').filter(|l| l.len() >= 2);
+    for (line, row) in rows.zip(tiles) {
+        for (c, tile) in line.chars().zip(row) {
+            let c = if c == 'S' { s_shape } else { c };
+            let is_pipe = get_glyph(c) != c;
+            let cell = match (tile, colour) {
+                (Tile::Loop(_), false) => get_glyph(c).to_string(),
+                (Tile::Inside, false) => "I".to_string(),
+                (Tile::Outside, false) => "O".to_string(),
+                (Tile::Loop(d), true) if distances => {
+                    //6 steps along the red axis of the 256 colour cube
+                    let red = (d * 5).checked_div(max_dist).unwrap_or(0);
+                    format!(
+                        "\x1b[38;5;{}m{}\x1b[0m",
+                        16 + 36 * red + (5 - red),
+                        get_glyph(c)
+                    )
+                }
+                (Tile::Loop(_), true) => format!("\x1b[1m{}\x1b[0m", get_glyph(c)),
+                (Tile::Inside, true) if is_pipe => format!("\x1b[2;32m{}\x1b[0m", get_glyph(c)),
+                (Tile::Inside, true) => "\x1b[32mI\x1b[0m".to_string(),
+                (Tile::Outside, true) if is_pipe => format!("\x1b[2m{}\x1b[0m", get_glyph(c)),
+                (Tile::Outside, true) => "\x1b[2mO\x1b[0m".to_string(),
+            };
+            output.push_str(&cell);
         }
         output.push('\n');
     }
-    fs::write("data/day10_ff.txt", output).expect("cannot write flood result");
-    expanded_map.values().filter(|c| **c != '#' && **c != ' ').count() as u64
+    output
 }
 
-fn p2(file: &str, p2_file: &str) -> u64 {
+fn solve_loop(lines: &str) -> (Vec<Vec<Tile>>, char) {
     let mut map: HashMap<(u64, u64), Vec<(u64, u64)>> = HashMap::new();
-    let s_loc = load_map(file, &mut map);
-    find_s_mappings(s_loc, &mut map);
+    let s_loc = parse_map(lines, &mut map);
+    let s_shape = find_s_mappings(s_loc, get_size(lines), &mut map).expect("Cannot place S");
     let mut dist_from_s: HashMap<(u64, u64), u64> = HashMap::new();
 
-    let points = traverse_from_s(s_loc.unwrap(), &map, &mut dist_from_s);
+    traverse_from_s(s_loc.unwrap(), &map, &mut dist_from_s);
 
-    write_loop(file, p2_file, points);
-    file.to_string().push_str(".ff");
-    flood_fill(p2_file)
+    (
+        classify_tiles(lines, &dist_from_s, s_loc.unwrap(), s_shape),
+        s_shape,
+    )
 }
 
-const STACK_SIZE: usize = 40 * 1024 * 1024;
-fn run() {
-    let file = "data/day10.txt";
-    let file_p2 = "data/day10_p2.txt";
-    println!("p1: {}", p1(file));
-    println!("p2: {}", p2(file, file_p2));
+fn p2(lines: &str) -> u64 {
+    count_enclosed(&solve_loop(lines).0)
 }
+
 fn main() {
-    // Spawn thread with explicit stack size
-    let child = thread::Builder::new()
-        .stack_size(STACK_SIZE)
-        .spawn(run)
-        .unwrap();
-
-    // Wait for thread to join
-    child.join().unwrap();
+    let lines = fs::read_to_string("data/day10.txt").expect("file not found");
+    println!("p1: {}", p1(&lines));
+    println!("p2: {}", p2(&lines));
+    println!("p2 (flood fill): {}", p2_flood_fill(&lines));
+
+    //day10 render [plain|dist]
+    let args: Vec<String> = env::args().collect();
+    if args.len() > 1 && args[1] == "render" {
+        let mode = args.get(2).map(|m| m.as_str());
+        let (tiles, s_shape) = solve_loop(&lines);
+        let colour = mode != Some("plain");
+        let distances = mode == Some("dist");
+        print!("{}", render(&lines, &tiles, s_shape, colour, distances));
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const SIZE: Size = (140, 140);
+    const LAST: u64 = 139;
+
     #[test]
     fn test_cell_exist() {
         let c = (0, 0);
-        assert!(is_cell_exist(&c));
-        let c = (GRID_MAX, 0);
-        assert!(is_cell_exist(&c));
-        let c = (0, GRID_MAX);
-        assert!(is_cell_exist(&c));
-        let c = (GRID_MAX, GRID_MAX);
-        assert!(is_cell_exist(&c));
-        let c = (0, GRID_MAX + 1);
-        assert!(!is_cell_exist(&c));
-        let c = (GRID_MAX + 1, 0);
-        assert!(!is_cell_exist(&c));
+        assert!(is_cell_exist(&c, SIZE));
+        let c = (LAST, 0);
+        assert!(is_cell_exist(&c, SIZE));
+        let c = (0, LAST);
+        assert!(is_cell_exist(&c, SIZE));
+        let c = (LAST, LAST);
+        assert!(is_cell_exist(&c, SIZE));
+        let c = (0, LAST + 1);
+        assert!(!is_cell_exist(&c, SIZE));
+        let c = (LAST + 1, 0);
+        assert!(!is_cell_exist(&c, SIZE));
+
+        //rows and columns are bounded separately
+        assert!(is_cell_exist(&(4, 0), (5, 3)));
+        assert!(!is_cell_exist(&(0, 4), (5, 3)));
+    }
+
+    #[test]
+    fn test_get_size() {
+        assert_eq!(get_size(".....\n.S-7.\n.|.|.\n"), (3, 5));
+        assert_eq!(get_size(""), (0, 0));
     }
 
     #[test]
     fn test_add_connection() {
         let mut map: HashMap<(u64, u64), Vec<(u64, u64)>> = HashMap::new();
-        add_connection_if_exist(1, 1, Some(0), Some(1), &mut map);
+        add_connection_if_exist(1, 1, Some(0), Some(1), SIZE, &mut map);
         assert_eq!(map.len(), 1);
         assert_eq!(map.contains_key(&(1, 1)), true);
         assert_eq!(map.get(&(1, 1)).unwrap().len(), 1);
 
         let mut map: HashMap<(u64, u64), Vec<(u64, u64)>> = HashMap::new();
-        add_connection_if_exist(0, 0, None, Some(0), &mut map);
+        add_connection_if_exist(0, 0, None, Some(0), SIZE, &mut map);
         assert_eq!(map.len(), 1);
         assert_eq!(map.contains_key(&(0, 0)), true);
         assert_eq!(map.get(&(0, 0)).unwrap().len(), 0);
 
         let mut map: HashMap<(u64, u64), Vec<(u64, u64)>> = HashMap::new();
-        add_connection_if_exist(GRID_MAX, GRID_MAX, Some(GRID_MAX), None, &mut map);
+        add_connection_if_exist(LAST, LAST, Some(LAST), None, SIZE, &mut map);
         assert_eq!(map.len(), 1);
-        assert_eq!(map.contains_key(&(GRID_MAX, GRID_MAX)), true);
-        assert_eq!(map.get(&(GRID_MAX, GRID_MAX)).unwrap().len(), 0);
+        assert_eq!(map.contains_key(&(LAST, LAST)), true);
+        assert_eq!(map.get(&(LAST, LAST)).unwrap().len(), 0);
 
         let mut map: HashMap<(u64, u64), Vec<(u64, u64)>> = HashMap::new();
-        add_connection_if_exist(
-            GRID_MAX,
-            GRID_MAX,
-            Some(GRID_MAX + 1),
-            Some(GRID_MAX),
-            &mut map,
-        );
+        add_connection_if_exist(LAST, LAST, Some(LAST + 1), Some(LAST), SIZE, &mut map);
         assert_eq!(map.len(), 1);
-        assert_eq!(map.contains_key(&(GRID_MAX, GRID_MAX)), true);
-        assert_eq!(map.get(&(GRID_MAX, GRID_MAX)).unwrap().len(), 0);
+        assert_eq!(map.contains_key(&(LAST, LAST)), true);
+        assert_eq!(map.get(&(LAST, LAST)).unwrap().len(), 0);
     }
 
     #[test]
     fn test_add_connection_by_symbol() {
         let mut map: HashMap<(u64, u64), Vec<(u64, u64)>> = HashMap::new();
-        add_connections(1, 1, '|', &mut map);
+        add_connections(1, 1, '|', SIZE, &mut map);
         assert_eq!(map.len(), 1);
         assert_eq!(map.contains_key(&(1, 1)), true);
         assert_eq!(map.get(&(1, 1)).unwrap().len(), 2);
@@ -399,7 +509,7 @@ mod tests {
         assert_eq!(map.get(&(1, 1)).unwrap().contains(&(2, 1)), true);
 
         let mut map: HashMap<(u64, u64), Vec<(u64, u64)>> = HashMap::new();
-        add_connections(1, 1, '-', &mut map);
+        add_connections(1, 1, '-', SIZE, &mut map);
         assert_eq!(map.len(), 1);
         assert_eq!(map.contains_key(&(1, 1)), true);
         assert_eq!(map.get(&(1, 1)).unwrap().len(), 2);
@@ -407,7 +517,7 @@ mod tests {
         assert_eq!(map.get(&(1, 1)).unwrap().contains(&(1, 2)), true);
 
         let mut map: HashMap<(u64, u64), Vec<(u64, u64)>> = HashMap::new();
-        add_connections(1, 1, 'L', &mut map);
+        add_connections(1, 1, 'L', SIZE, &mut map);
         assert_eq!(map.len(), 1);
         assert_eq!(map.contains_key(&(1, 1)), true);
         assert_eq!(map.get(&(1, 1)).unwrap().len(), 2);
@@ -415,7 +525,7 @@ mod tests {
         assert_eq!(map.get(&(1, 1)).unwrap().contains(&(1, 2)), true);
 
         let mut map: HashMap<(u64, u64), Vec<(u64, u64)>> = HashMap::new();
-        add_connections(1, 1, 'J', &mut map);
+        add_connections(1, 1, 'J', SIZE, &mut map);
         assert_eq!(map.len(), 1);
         assert_eq!(map.contains_key(&(1, 1)), true);
         assert_eq!(map.get(&(1, 1)).unwrap().len(), 2);
@@ -423,7 +533,7 @@ mod tests {
         assert_eq!(map.get(&(1, 1)).unwrap().contains(&(1, 0)), true);
 
         let mut map: HashMap<(u64, u64), Vec<(u64, u64)>> = HashMap::new();
-        add_connections(1, 1, '7', &mut map);
+        add_connections(1, 1, '7', SIZE, &mut map);
         assert_eq!(map.len(), 1);
         assert_eq!(map.contains_key(&(1, 1)), true);
         assert_eq!(map.get(&(1, 1)).unwrap().len(), 2);
@@ -431,7 +541,7 @@ mod tests {
         assert_eq!(map.get(&(1, 1)).unwrap().contains(&(1, 0)), true);
 
         let mut map: HashMap<(u64, u64), Vec<(u64, u64)>> = HashMap::new();
-        add_connections(1, 1, 'F', &mut map);
+        add_connections(1, 1, 'F', SIZE, &mut map);
         assert_eq!(map.len(), 1);
         assert_eq!(map.contains_key(&(1, 1)), true);
         assert_eq!(map.get(&(1, 1)).unwrap().len(), 2);
@@ -439,13 +549,13 @@ mod tests {
         assert_eq!(map.get(&(1, 1)).unwrap().contains(&(1, 2)), true);
 
         let mut map: HashMap<(u64, u64), Vec<(u64, u64)>> = HashMap::new();
-        add_connections(1, 1, '.', &mut map);
+        add_connections(1, 1, '.', SIZE, &mut map);
         assert_eq!(map.len(), 1);
         assert_eq!(map.contains_key(&(1, 1)), true);
         assert_eq!(map.get(&(1, 1)).unwrap().len(), 0);
 
         let mut map: HashMap<(u64, u64), Vec<(u64, u64)>> = HashMap::new();
-        add_connections(1, 1, 'S', &mut map);
+        add_connections(1, 1, 'S', SIZE, &mut map);
         assert_eq!(map.len(), 1);
         assert_eq!(map.contains_key(&(1, 1)), true);
         assert_eq!(map.get(&(1, 1)).unwrap().len(), 0);
@@ -456,7 +566,7 @@ mod tests {
         let file = "data/day10_ex.txt";
         let mut map: HashMap<(u64, u64), Vec<(u64, u64)>> = HashMap::new();
 
-        load_map(file, &mut map);
+        parse_map(&fs::read_to_string(file).expect("file not found"), &mut map);
 
         assert_eq!(map.get(&(1, 1)).unwrap().len(), 0); //s
         assert_eq!(map.get(&(1, 2)).unwrap().len(), 2);
@@ -476,8 +586,8 @@ mod tests {
     fn test_find_s() {
         let file = "data/day10_ex.txt";
         let mut map: HashMap<(u64, u64), Vec<(u64, u64)>> = HashMap::new();
-        let s = load_map(file, &mut map);
-        find_s_mappings(s, &mut map);
+        let s = parse_map(&fs::read_to_string(file).expect("file not found"), &mut map);
+        assert_eq!(find_s_mappings(s, SIZE, &mut map), Ok('F'));
 
         assert_eq!(map.get(&(1, 1)).unwrap().len(), 2); //s
         assert_eq!(map.get(&(1, 1)).unwrap().contains(&(1, 2)), true);
@@ -488,9 +598,9 @@ mod tests {
     fn test_move_from_s() {
         let file = "data/day10_ex.txt";
         let mut map: HashMap<(u64, u64), Vec<(u64, u64)>> = HashMap::new();
-        let s = load_map(file, &mut map);
+        let s = parse_map(&fs::read_to_string(file).expect("file not found"), &mut map);
         let mut dist_from_s: HashMap<(u64, u64), u64> = HashMap::new();
-        find_s_mappings(s, &mut map);
+        find_s_mappings(s, SIZE, &mut map).unwrap();
 
         traverse_from_s(s.unwrap(), &map, &mut dist_from_s);
         assert_eq!(*dist_from_s.get(&(3, 3)).unwrap(), 4);
@@ -504,8 +614,112 @@ mod tests {
 
     #[test]
     fn test_p1() {
-        let file = "data/day10_ex.txt";
-        assert_eq!(p1(file), 4);
+        let lines = fs::read_to_string("data/day10_ex.txt").expect("file not found");
+        assert_eq!(p1(&lines), 4);
+    }
+
+    #[test]
+    fn test_p1_in_memory() {
+        let lines = "..F7.\n.FJ|.\nSJ.L7\n|F--J\nLJ...\n";
+        assert_eq!(p1(lines), 8);
+    }
+
+    #[test]
+    fn test_p2() {
+        let lines = "\
+...........
+.S-------7.
+.|F-----7|.
+.||.....||.
+.||.....||.
+.|L-7.F-J|.
+.|..|.|..|.
+.L--J.L--J.
+...........
+";
+        assert_eq!(p2(lines), 4);
+        assert_eq!(p2_flood_fill(lines), 4);
+
+        let lines = "\
+.F----7F7F7F7F-7....
+.|F--7||||||||FJ....
+.||.FJ||||||||L7....
+FJL7L7LJLJ||LJ.L-7..
+L--J.L7...LJS7F-7L7.
+....F-J..F7FJ|L7L7L7
+....L7.F7||L7|.L7L7|
+.....|FJLJ|FJ|F7|.LJ
+....FJL-7.||.||||...
+....L---J.LJ.LJLJ...
+";
+        assert_eq!(p2(lines), 8);
+        assert_eq!(p2_flood_fill(lines), 8);
+    }
+
+    #[test]
+    fn test_s_shape() {
+        let shape = |lines: &str| {
+            let mut map: HashMap<(u64, u64), Vec<(u64, u64)>> = HashMap::new();
+            let s = parse_map(lines, &mut map);
+            find_s_mappings(s, get_size(lines), &mut map)
+        };
+        assert_eq!(shape(".....\n.S-7.\n.|.|.\n.L-J.\n"), Ok('F'));
+        assert_eq!(shape(".....\n.F-7.\n.|.|.\n.L-S.\n"), Ok('J'));
+        assert_eq!(shape(".....\n.F-7.\n.S.|.\n.L-J.\n"), Ok('|'));
+        assert_eq!(shape("S-7\n|.|\nL-J\n"), Ok('F'));
+
+        //pipes pointing at S from three sides, or only one
+        assert!(shape(".|...\n-S-7.\n.|.|.\n.L-J.\n").is_err());
+        assert!(shape("..\n.S-\n..\n").is_err());
+        assert!(shape("...\n...\n").is_err());
+    }
+
+    #[test]
+    fn test_s_shape_parity() {
+        //S as '|' counts as a crossing, S as '-' doesn't
+        let lines = "\
+F-7.F-7
+|.|.|.|
+S.L-J.|
+|.....|
+L-----J
+";
+        assert_eq!(p2(lines), 9);
+        assert_eq!(p2_flood_fill(lines), 9);
+        let lines = "\
+F-S-7.
+|...|.
+L---J.
+";
+        assert_eq!(p2(lines), 3);
+        assert_eq!(p2_flood_fill(lines), 3);
+    }
+
+    #[test]
+    fn test_large_maze() {
+        //one big ring full of junk pipes, far too deep for a recursive fill
+        let size = 600;
+        let mut rows = vec![format!("S{}7", "-".repeat(size - 2))];
+        for _ in 0..size - 2 {
+            rows.push(format!("|{}|", "|".repeat(size - 2)));
+        }
+        rows.push(format!("L{}J", "-".repeat(size - 2)));
+        let lines = rows.join("\n");
+        let expected = ((size - 2) * (size - 2)) as u64;
+        assert_eq!(p2(&lines), expected);
+        assert_eq!(p2_flood_fill(&lines), expected);
+    }
+
+    #[test]
+    fn test_p2_wide_grid() {
+        //wider than it is tall, and larger than the old fixed grid
+        let width = 300;
+        let mut lines = String::new();
+        lines.push_str(&format!("S{}7\n", "-".repeat(width - 2)));
+        lines.push_str(&format!("|{}|\n", ".".repeat(width - 2)));
+        lines.push_str(&format!("L{}J\n", "-".repeat(width - 2)));
+        assert_eq!(p1(&lines), width as u64 + 1);
+        assert_eq!(p2(&lines), width as u64 - 2);
     }
 
     #[test]
@@ -526,4 +740,41 @@ mod tests {
         let cur = (1, 0);
         assert_eq!(get_extra_line_elem(prev, cur), (3, 2));
     }
+
+    #[test]
+    fn test_classify_tiles() {
+        let lines = ".....\n.S-7.\n.|.|.\n.L-J.\n.....\n";
+        let (tiles, s_shape) = solve_loop(lines);
+        assert_eq!(s_shape, 'F');
+        assert_eq!(tiles[1][1], Tile::Loop(0));
+        assert_eq!(tiles[3][3], Tile::Loop(4));
+        assert_eq!(tiles[2][2], Tile::Inside);
+        assert_eq!(tiles[2][0], Tile::Outside);
+        assert_eq!(tiles[2][4], Tile::Outside);
+        assert_eq!(count_enclosed(&tiles), 1);
+    }
+
+    #[test]
+    fn test_render() {
+        let lines = "-L|F7\n7S-7|\nL|7||\n-L-J|\nL|-JF\n";
+        let (tiles, s_shape) = solve_loop(lines);
+        let expected = "\
+OOOOO
+O┌─┐O
+O│I│O
+O└─┘O
+OOOOO
+";
+        assert_eq!(render(lines, &tiles, s_shape, false, false), expected);
+
+        let coloured = render(lines, &tiles, s_shape, true, false);
+        assert!(coloured.starts_with("\x1b[2m─\x1b[0m\x1b[2m└\x1b[0m"));
+        assert!(coloured.contains("\x1b[1m┌\x1b[0m"));
+        assert!(coloured.contains("\x1b[2;32m┐\x1b[0m"));
+
+        //S is nearest, the far corner furthest
+        let shaded = render(lines, &tiles, s_shape, true, true);
+        assert!(shaded.contains("\x1b[38;5;21m┌"));
+        assert!(shaded.contains("\x1b[38;5;196m┘"));
+    }
 }
@@ -1,82 +1,34 @@
-use std::{
-    cmp::{max, min},
-    fs::*,
-};
+use std::{collections::BTreeMap, env, fs::*};
 
 use num::abs;
 
-fn expand_universe(file: &str, file_expanded: &str) -> (Vec<u64>, Vec<u64>) {
-    let result = read_to_string(file).unwrap();
-    let lines = result.split_whitespace().collect::<Vec<_>>();
-
-    let length: u64 = lines[0].len() as u64;
-    let mut i: u64 = 0;
-    let mut j: u64 = 0;
+fn find_empty(lines: &str) -> (Vec<u64>, Vec<u64>) {
+    let lines = lines.split_whitespace().collect::<Vec<_>>();
+    let width = lines.iter().map(|l| l.len()).max().unwrap_or(0);
     let mut empty_rows: Vec<u64> = Vec::new();
-    let mut empty_cols: Vec<u64> = Vec::new();
-    let mut filled_cols: Vec<u64> = Vec::new();
-    let mut galaxies: Vec<(u64, u64)> = Vec::new();
-    for l in lines {
+    let mut filled_cols = vec![false; width];
+
+    for (i, l) in lines.iter().enumerate() {
         let mut empty = true;
-        for c in l.chars() {
+        for (j, c) in l.chars().enumerate() {
             if c == '#' {
                 empty = false;
-                filled_cols.push(j);
-                galaxies.push((i, j));
+                filled_cols[j] = true;
             }
-            j += 1;
         }
-
         if empty {
-            empty_rows.push(i);
-        }
-        i += 1;
-        j = 0;
-    }
-
-    for j in 0..length {
-        if !filled_cols.contains(&j) {
-            empty_cols.push(j);
+            empty_rows.push(i as u64);
         }
     }
 
-    //print
-    let mut output = String::new();
-
-    for i in 0..length {
-        if empty_rows.contains(&i) {
-            let target_len = length + empty_cols.len() as u64;
-            for _j in 0..target_len {
-                output.push('.');
-            }
-            output.push('\n');
-            for _j in 0..target_len {
-                output.push('.');
-            }
-        } else {
-            for j in 0..length {
-                if empty_cols.contains(&j) {
-                    //write twice
-                    output.push_str("..");
-                } else if galaxies.contains(&(i, j)) {
-                    output.push('#');
-                } else {
-                    //normal space
-                    output.push('.');
-                }
-            }
-        }
-        if i != length - 1 {
-            output.push('\n');
-        }
-    }
-    write(file_expanded, output).expect("Cannot write expanded");
+    let empty_cols = (0..width as u64)
+        .filter(|j| !filled_cols[*j as usize])
+        .collect();
     (empty_rows, empty_cols)
 }
 
-fn load_galaxies(file: &str) -> Vec<(u64, u64)> {
-    let result = read_to_string(file).unwrap();
-    let lines = result.split_whitespace().collect::<Vec<_>>();
+fn parse_galaxies(lines: &str) -> Vec<(u64, u64)> {
+    let lines = lines.split_whitespace().collect::<Vec<_>>();
 
     let mut i: u64 = 0;
     let mut j: u64 = 0;
@@ -96,126 +48,263 @@ fn load_galaxies(file: &str) -> Vec<(u64, u64)> {
     galaxies
 }
 
+fn load_galaxies(file: &str) -> Vec<(u64, u64)> {
+    parse_galaxies(&read_to_string(file).unwrap())
+}
+
+//moves every galaxy out by scale - 1 for each empty row or column before it,
+//the empty lists are sorted so those are binary searches. A scale of 0 would
+//pull galaxies backwards, so scales must be at least 1
+fn expand(
+    galaxies: &[(u64, u64)],
+    empty_rows: &[u64],
+    empty_cols: &[u64],
+    row_scale: u64,
+    col_scale: u64,
+) -> Vec<(u64, u64)> {
+    assert!(
+        row_scale >= 1 && col_scale >= 1,
+        "Scales must be at least 1, got {} and {}",
+        row_scale,
+        col_scale
+    );
+    galaxies
+        .iter()
+        .map(|g| {
+            let rows_before = empty_rows.partition_point(|r| *r < g.0) as u64;
+            let cols_before = empty_cols.partition_point(|c| *c < g.1) as u64;
+            (
+                g.0 + rows_before * (row_scale - 1),
+                g.1 + cols_before * (col_scale - 1),
+            )
+        })
+        .collect()
+}
+
 fn get_dist(me: &(u64, u64), other: &(u64, u64)) -> u64 {
     abs(other.0 as i64 - me.0 as i64) as u64 + abs(other.1 as i64 - me.1 as i64) as u64
 }
 
-fn get_dist_p2(
-    me: &(u64, u64),
-    other: &(u64, u64),
-    empty_rows: &Vec<u64>,
-    empty_cols: &Vec<u64>,
-    scale: u64,
-) -> u64 {
-    let from_row = min(me.0, other.0);
-    let to_row = max(me.0, other.0);
-    let from_col = min(me.1, other.1);
-    let to_col = max(me.1, other.1);
-
-    let empty_row_count = empty_rows
-        .iter()
-        .filter(|r| **r > from_row && **r < to_row)
-        .count() as u64;
-    let empty_col_count = empty_cols
-        .iter()
-        .filter(|c| **c > from_col && **c < to_col)
-        .count() as u64;
+//sum of |a - b| over all pairs: once sorted, each value is added for every
+//smaller value before it and the sum of those values is taken away
+fn sum_axis_distances(mut values: Vec<u64>) -> u128 {
+    values.sort();
+    let mut result: u128 = 0;
+    let mut prefix: u128 = 0;
+    for (i, v) in values.iter().enumerate() {
+        result += *v as u128 * i as u128 - prefix;
+        prefix += *v as u128;
+    }
+    result
+}
 
-    let vert_dist =
-        empty_row_count * scale + abs(other.0 as i64 - me.0 as i64) as u64 - empty_row_count;
-    let hori_dist =
-        empty_col_count * scale + abs(other.1 as i64 - me.1 as i64) as u64 - empty_col_count;
+fn sum_all_pairs(galaxies: &[(u64, u64)]) -> u128 {
+    sum_axis_distances(galaxies.iter().map(|g| g.0).collect())
+        + sum_axis_distances(galaxies.iter().map(|g| g.1).collect())
+}
 
-    vert_dist + hori_dist
+//expanded galaxies for answering questions about single galaxies or pairs
+struct Universe {
+    galaxies: Vec<(u64, u64)>,
+    by_row: Vec<usize>,   //galaxy indexes sorted by row
+    row_rank: Vec<usize>, //position of each galaxy in by_row
+    //the farthest galaxy is always one of the extremes of row + col or row - col
+    extremes: Vec<(i128, usize)>,
 }
 
-fn p1(galaxies: &Vec<(u64, u64)>) -> u64 {
-    let mut result = 0;
-    let r = Vec::new();
-    let c = Vec::new();
+impl Universe {
+    fn new(
+        galaxies: &[(u64, u64)],
+        empty_rows: &[u64],
+        empty_cols: &[u64],
+        scale: u64,
+    ) -> Universe {
+        let galaxies = expand(galaxies, empty_rows, empty_cols, scale, scale);
+        let mut by_row: Vec<usize> = (0..galaxies.len()).collect();
+        by_row.sort_by_key(|i| galaxies[*i]);
+        let mut row_rank = vec![0; galaxies.len()];
+        for (rank, i) in by_row.iter().enumerate() {
+            row_rank[*i] = rank;
+        }
+
+        let mut extremes = Vec::new();
+        for sign in [1, -1] {
+            let with_key = galaxies
+                .iter()
+                .enumerate()
+                .map(|(i, g)| (g.0 as i128 + sign * g.1 as i128, i));
+            extremes.extend(with_key.clone().min());
+            extremes.extend(with_key.max());
+        }
 
-    for g in galaxies {
-        result += get_dist_to_others(g, galaxies, false, &r, &c, 1);
+        Universe {
+            galaxies,
+            by_row,
+            row_rank,
+            extremes,
+        }
     }
-    result / 2
-}
 
-fn get_dist_to_others(
-    me: &(u64, u64),
-    galaxies: &Vec<(u64, u64)>,
-    is_p2: bool,
-    empty_rows: &Vec<u64>,
-    empty_cols: &Vec<u64>,
-    scale: u64,
-) -> u64 {
-    galaxies
-        .iter()
-        .map(|g| {
-            if is_p2 {
-                get_dist_p2(me, g, empty_rows, empty_cols, scale)
-            } else {
-                get_dist(me, g)
+    fn dist(&self, a: usize, b: usize) -> u64 {
+        get_dist(&self.galaxies[a], &self.galaxies[b])
+    }
+
+    //(galaxy, distance), ties go to the lower index
+    fn nearest(&self, a: usize) -> Option<(usize, u64)> {
+        //walk away from a in row order until the row gap alone is too far
+        let mut best: Option<(u64, usize)> = None;
+        let rank = self.row_rank[a];
+        let before = self.by_row[..rank].iter().rev();
+        let after = self.by_row[rank + 1..].iter();
+        for side in [before.collect::<Vec<_>>(), after.collect::<Vec<_>>()] {
+            for b in side {
+                let row_gap = self.galaxies[a].0.abs_diff(self.galaxies[*b].0);
+                if best.is_some_and(|(d, _)| row_gap > d) {
+                    break;
+                }
+                let candidate = (self.dist(a, *b), *b);
+                if best.is_none_or(|current| candidate < current) {
+                    best = Some(candidate);
+                }
             }
-        })
-        .sum()
-}
+        }
+        best.map(|(d, b)| (b, d))
+    }
 
-fn p2(galaxies: &Vec<(u64, u64)>, empty_rows: &Vec<u64>, empty_cols: &Vec<u64>, scale: u64) -> u64 {
-    let mut result = 0;
+    fn farthest(&self, a: usize) -> Option<(usize, u64)> {
+        self.extremes
+            .iter()
+            .filter(|(_, b)| *b != a)
+            .map(|(_, b)| (self.dist(a, *b), *b))
+            .max_by_key(|(d, b)| (*d, std::cmp::Reverse(*b)))
+            .map(|(d, b)| (b, d))
+    }
 
-    for g in galaxies {
-        result += get_dist_to_others(g, galaxies, true, empty_rows, empty_cols, scale);
+    //number of pairs per distance, grouped into buckets of the given width,
+    //a width of 0 keeps every distance apart
+    fn histogram(&self, bucket: u64) -> BTreeMap<u64, u64> {
+        let mut result = BTreeMap::new();
+        for a in 0..self.galaxies.len() {
+            for b in a + 1..self.galaxies.len() {
+                let d = self.dist(a, b);
+                *result
+                    .entry(d - d.checked_rem(bucket).unwrap_or(0))
+                    .or_insert(0) += 1;
+            }
+        }
+        result
     }
-    result / 2
 }
 
-fn main() {
-    let file = "data/day11.txt";
-    let file_expanded = "data/day11_expanded.txt";
-    let empty_data = expand_universe(file, file_expanded);
-    let gx: Vec<(u64, u64)> = load_galaxies(file_expanded);
+fn p1(galaxies: &[(u64, u64)], empty_rows: &[u64], empty_cols: &[u64]) -> u128 {
+    sum_all_pairs(&expand(galaxies, empty_rows, empty_cols, 2, 2))
+}
 
-    println!("p1: {}", p1(&gx));
+fn p2(galaxies: &[(u64, u64)], empty_rows: &[u64], empty_cols: &[u64], scale: u64) -> u128 {
+    sum_all_pairs(&expand(galaxies, empty_rows, empty_cols, scale, scale))
+}
 
-    //p2
-    /*
-       1. load orig galaxies
-       2. find orig empty rows, cols (already in empty_data)
-       3. accomodate empty rows cols in dist calculation
-    */
+//scale factors from the command line, 0 isn't a valid expansion
+fn parse_scale(arg: &str) -> Result<u64, String> {
+    match arg.parse::<u64>() {
+        Ok(0) => Err(format!("Scale must be at least 1: {}", arg)),
+        Ok(scale) => Ok(scale),
+        Err(e) => Err(format!("Bad scale {}: {}", arg, e)),
+    }
+}
 
+fn main() {
+    let file = "data/day11.txt";
     let gx: Vec<(u64, u64)> = load_galaxies(file);
-    println!("p2: {}", p2(&gx, &empty_data.0, &empty_data.1, 1000000));
+    let (empty_rows, empty_cols) = find_empty(&read_to_string(file).unwrap());
+
+    println!("p1: {}", p1(&gx, &empty_rows, &empty_cols));
+    println!("p2: {}", p2(&gx, &empty_rows, &empty_cols, 1000000));
+
+    //day11 query <scale> <galaxy> <galaxy>, day11 histogram <scale> <bucket>,
+    //or day11 <row scale> <col scale>
+    let args: Vec<String> = env::args().collect();
+    if args.len() == 5 && args[1] == "query" {
+        let scale = match parse_scale(&args[2]) {
+            Ok(scale) => scale,
+            Err(e) => return println!("{}", e),
+        };
+        let a = args[3].parse::<usize>().expect(&args[3]);
+        let b = args[4].parse::<usize>().expect(&args[4]);
+        let universe = Universe::new(&gx, &empty_rows, &empty_cols, scale);
+        println!("{} to {}: {}", a, b, universe.dist(a, b));
+        for g in [a, b] {
+            if let (Some(near), Some(far)) = (universe.nearest(g), universe.farthest(g)) {
+                println!(
+                    "{}: nearest {} ({}), farthest {} ({})",
+                    g, near.0, near.1, far.0, far.1
+                );
+            }
+        }
+    } else if args.len() == 4 && args[1] == "histogram" {
+        let scale = match parse_scale(&args[2]) {
+            Ok(scale) => scale,
+            Err(e) => return println!("{}", e),
+        };
+        let bucket = args[3].parse::<u64>().expect(&args[3]);
+        let universe = Universe::new(&gx, &empty_rows, &empty_cols, scale);
+        for (d, count) in universe.histogram(bucket) {
+            if bucket > 1 {
+                println!("{}..{}: {}", d, d + bucket, count);
+            } else {
+                println!("{}: {}", d, count);
+            }
+        }
+    } else if args.len() == 3 {
+        let (row_scale, col_scale) = match (parse_scale(&args[1]), parse_scale(&args[2])) {
+            (Ok(row_scale), Ok(col_scale)) => (row_scale, col_scale),
+            (Err(e), _) | (_, Err(e)) => return println!("{}", e),
+        };
+        let expanded = expand(&gx, &empty_rows, &empty_cols, row_scale, col_scale);
+        println!(
+            "rows x{}, cols x{}: {}",
+            row_scale,
+            col_scale,
+            sum_all_pairs(&expanded)
+        );
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    const EXAMPLE: &str = "\
+...#......
+.......#..
+#.........
+..........
+......#...
+.#........
+.........#
+..........
+.......#..
+#...#.....
+";
 
     #[test]
-    fn test_expand() {
-        let file = "data/day11_ex.txt";
-        let file_expanded = "data/day11_ex_expanded.txt";
-        let empties = expand_universe(file, file_expanded);
-
-        let result = read_to_string(file_expanded).unwrap();
-        let lines = result.split_whitespace().collect::<Vec<_>>();
-        assert_eq!(lines.len(), 12);
-        assert_eq!(lines[0].len(), 13);
-
-        assert_eq!(empties.0.len(), 2);
-        assert_eq!(empties.0[0], 3);
-        assert_eq!(empties.0[1], 7);
-        assert_eq!(empties.1.len(), 3);
-        assert_eq!(empties.1[0], 2);
-        assert_eq!(empties.1[1], 5);
-        assert_eq!(empties.1[2], 8);
+    fn test_find_empty() {
+        let (empty_rows, empty_cols) = find_empty(EXAMPLE);
+        assert_eq!(empty_rows, vec![3, 7]);
+        assert_eq!(empty_cols, vec![2, 5, 8]);
+
+        //not square
+        let (empty_rows, empty_cols) = find_empty("#....\n.....\n...#.\n");
+        assert_eq!(empty_rows, vec![1]);
+        assert_eq!(empty_cols, vec![1, 2, 4]);
     }
 
     #[test]
-    fn test_load_galaxies() {
-        let file = "data/day11_ex_expanded.txt";
-        let result: Vec<(u64, u64)> = load_galaxies(file);
+    fn test_expand() {
+        let gx = parse_galaxies(EXAMPLE);
+        let (empty_rows, empty_cols) = find_empty(EXAMPLE);
+        let result = expand(&gx, &empty_rows, &empty_cols, 2, 2);
 
         assert_eq!(result.len(), 9);
         assert!(result.contains(&(0, 4)));
@@ -227,81 +316,168 @@ mod tests {
         assert!(result.contains(&(10, 9)));
         assert!(result.contains(&(11, 0)));
         assert!(result.contains(&(11, 5)));
+
+        //rows and columns grow separately
+        let result = expand(&gx, &empty_rows, &empty_cols, 1, 3);
+        assert!(result.contains(&(4, 10)));
+        assert!(result.contains(&(6, 15)));
+        let result = expand(&gx, &empty_rows, &empty_cols, 3, 1);
+        assert!(result.contains(&(8, 9)));
+        assert!(result.contains(&(13, 4)));
+    }
+
+    #[test]
+    fn test_expand_scales() {
+        //empty case, same as p1
+        let me = (0, 0);
+        let other = (2, 1);
+        let expanded = expand(&[me, other], &[], &[], 1, 1);
+        assert_eq!(get_dist(&expanded[0], &expanded[1]), get_dist(&me, &other));
+
+        let expanded = expand(&[me, other], &[1], &[2], 2, 2);
+        assert_eq!(get_dist(&expanded[0], &expanded[1]), 4);
+        let expanded = expand(&[me, other], &[1], &[2], 10, 10);
+        assert_eq!(get_dist(&expanded[0], &expanded[1]), 12);
+
+        //horizontal
+        let other = (1, 2);
+        let expanded = expand(&[me, other], &[], &[1], 2, 2);
+        assert_eq!(get_dist(&expanded[0], &expanded[1]), 4);
+        let expanded = expand(&[me, other], &[], &[1], 10, 10);
+        assert_eq!(get_dist(&expanded[0], &expanded[1]), 12);
+    }
+
+    #[test]
+    #[should_panic(expected = "Scales must be at least 1, got 2 and 0")]
+    fn test_expand_zero_scale() {
+        expand(&[(0, 0), (2, 2)], &[1], &[1], 2, 0);
+    }
+
+    #[test]
+    fn test_parse_scale() {
+        assert_eq!(parse_scale("1"), Ok(1));
+        assert_eq!(parse_scale("1000000"), Ok(1000000));
+        assert!(parse_scale("0").is_err());
+        assert!(parse_scale("-1").is_err());
+        assert!(parse_scale("x").is_err());
+    }
+
+    #[test]
+    fn test_p1() {
+        let gx = parse_galaxies(EXAMPLE);
+        let (empty_rows, empty_cols) = find_empty(EXAMPLE);
+        assert_eq!(p1(&gx, &empty_rows, &empty_cols), 374);
     }
 
     #[test]
     fn test_p2() {
-        let file = "data/day11_ex.txt";
-        let file_expanded = "data/day11_ex_expanded.txt";
-        let empties = expand_universe(file, file_expanded);
-        let gx: Vec<(u64, u64)> = load_galaxies(file);
+        let gx = parse_galaxies(EXAMPLE);
+        let (empty_rows, empty_cols) = find_empty(EXAMPLE);
 
         let scale = 10;
-        let result = p2(&gx, &empties.0, &empties.1, scale);
+        let result = p2(&gx, &empty_rows, &empty_cols, scale);
         assert_eq!(result, 1030);
         let scale = 100;
-        let result = p2(&gx, &empties.0, &empties.1, scale);
+        let result = p2(&gx, &empty_rows, &empty_cols, scale);
         assert_eq!(result, 8410);
     }
 
     #[test]
-    fn test_p1() {
-        let file = "data/day11_ex_expanded.txt";
-        let result: Vec<(u64, u64)> = load_galaxies(file);
-
-        let p1 = p1(&result);
-        assert_eq!(p1, 374);
+    fn test_sum_all_pairs() {
+        let mut rng = StdRng::seed_from_u64(43);
+        let gx: Vec<(u64, u64)> = (0..200)
+            .map(|_| (rng.gen_range(0..1000), rng.gen_range(0..1000)))
+            .collect();
+        let mut expected: u128 = 0;
+        for i in 0..gx.len() {
+            for j in i + 1..gx.len() {
+                expected += get_dist(&gx[i], &gx[j]) as u128;
+            }
+        }
+        assert_eq!(sum_all_pairs(&gx), expected);
+        assert_eq!(sum_all_pairs(&[]), 0);
     }
 
     #[test]
-    fn test_dist_to_others() {
-        let r = Vec::new();
-        let gx = vec![(0, 0), (0, 1), (1, 1)];
-        assert_eq!(get_dist_to_others(&(0, 0), &gx, false, &r, &r, 1), 3);
+    fn test_many_galaxies() {
+        //a diagonal of galaxies with an empty row and column between each
+        let n: u128 = 100_000;
+        let gx: Vec<(u64, u64)> = (0..n as u64).map(|i| (i * 2, i * 2)).collect();
+        let empty: Vec<u64> = (0..n as u64 - 1).map(|i| i * 2 + 1).collect();
+        let scale = 1_000_000;
+        let expanded = expand(&gx, &empty, &empty, scale, scale);
+        assert_eq!(expanded[1], (scale + 1, scale + 1));
+
+        //sum of j - i over pairs is n(n^2 - 1)/6, on both axes
+        let step = scale as u128 + 1;
+        assert_eq!(sum_all_pairs(&expanded), n * (n * n - 1) / 6 * step * 2);
     }
 
     #[test]
-    fn test_get_dist_p2() {
-        //empty case, same as p1
-        let empty_rows = Vec::new();
-        let empty_cols = Vec::new();
-
-        let scale = 1;
-
-        let me = (0, 0);
-        let other = (2, 1);
-        assert_eq!(
-            get_dist_p2(&me, &other, &empty_rows, &empty_cols, scale),
-            get_dist(&me, &other)
-        );
+    fn test_universe_dist() {
+        let gx = parse_galaxies(EXAMPLE);
+        let (empty_rows, empty_cols) = find_empty(EXAMPLE);
+        let universe = Universe::new(&gx, &empty_rows, &empty_cols, 2);
+        assert_eq!(universe.dist(4, 8), 9);
+        assert_eq!(universe.dist(0, 6), 15);
+        assert_eq!(universe.dist(2, 5), 17);
+        assert_eq!(universe.dist(7, 8), 5);
+        assert_eq!(universe.dist(8, 7), 5);
+
+        //galaxies 8 and 9 share a row, so only columns grow
+        let universe = Universe::new(&gx, &empty_rows, &empty_cols, 10);
+        assert_eq!(universe.dist(7, 8), 13);
+    }
 
-        //p2 case
-        let scale = 2;
-        let empty_rows = vec![1];
-        let empty_cols = vec![2];
-        assert_eq!(get_dist_p2(&me, &other, &empty_rows, &empty_cols, scale), 4);
-        let scale = 10;
-        let empty_rows = vec![1];
-        let empty_cols = vec![2];
-        assert_eq!(
-            get_dist_p2(&me, &other, &empty_rows, &empty_cols, scale),
-            12
-        );
+    #[test]
+    fn test_nearest_farthest() {
+        let gx = parse_galaxies(EXAMPLE);
+        let (empty_rows, empty_cols) = find_empty(EXAMPLE);
+        let universe = Universe::new(&gx, &empty_rows, &empty_cols, 2);
+        assert_eq!(universe.nearest(7), Some((8, 5)));
+        assert_eq!(universe.farthest(0), Some((6, 15)));
+        assert_eq!(Universe::new(&[(3, 3)], &[], &[], 2).nearest(0), None);
+        assert_eq!(Universe::new(&[(3, 3)], &[], &[], 2).farthest(0), None);
+
+        let mut rng = StdRng::seed_from_u64(44);
+        let gx: Vec<(u64, u64)> = (0..300)
+            .map(|_| (rng.gen_range(0..100), rng.gen_range(0..100)))
+            .collect();
+        let universe = Universe::new(&gx, &[10, 50], &[20], 5);
+        for a in 0..gx.len() {
+            let others = (0..gx.len()).filter(|b| *b != a);
+            let near = others.clone().min_by_key(|b| (universe.dist(a, *b), *b));
+            let far = others.max_by_key(|b| (universe.dist(a, *b), std::cmp::Reverse(*b)));
+            assert_eq!(
+                universe.nearest(a).map(|n| n.1),
+                near.map(|b| universe.dist(a, b))
+            );
+            assert_eq!(universe.nearest(a).map(|n| n.0), near);
+            assert_eq!(
+                universe.farthest(a).map(|f| f.1),
+                far.map(|b| universe.dist(a, b))
+            );
+        }
+    }
 
-        //horizontal
-        let me = (0, 0);
-        let other = (1, 2);
-        let scale = 2;
-        let empty_rows = vec![];
-        let empty_cols = vec![1];
-        assert_eq!(get_dist_p2(&me, &other, &empty_rows, &empty_cols, scale), 4);
-        let scale = 10;
-        let empty_rows = vec![];
-        let empty_cols = vec![1];
-        assert_eq!(
-            get_dist_p2(&me, &other, &empty_rows, &empty_cols, scale),
-            12
-        );
+    #[test]
+    fn test_histogram() {
+        let gx = parse_galaxies(EXAMPLE);
+        let (empty_rows, empty_cols) = find_empty(EXAMPLE);
+        let universe = Universe::new(&gx, &empty_rows, &empty_cols, 2);
+
+        let histogram = universe.histogram(1);
+        assert_eq!(histogram.values().sum::<u64>(), 36);
+        let total: u64 = histogram.iter().map(|(d, count)| d * count).sum();
+        assert_eq!(total, 374);
+        assert_eq!(histogram[&5], 4);
+
+        let histogram = universe.histogram(10);
+        assert_eq!(histogram.values().sum::<u64>(), 36);
+        assert!(histogram.keys().all(|d| d % 10 == 0));
+
+        //no bucketing
+        assert_eq!(universe.histogram(0), universe.histogram(1));
     }
 
     #[test]
@@ -1,267 +1,919 @@
-
+use num::{BigUint, Zero};
+use rand::Rng;
 use rayon::prelude::*;
-use std::{collections::{HashSet, HashMap}, fs};
-fn match_records(record: &str, row: &str) -> bool {
-    let spring_sets: Vec<u64> = record
-        .split(',')
-        .map(|s| s.parse::<u64>().unwrap())
-        .collect();
-    let spring_only = row.replace(".", " ");
-    let sets: Vec<&str> = spring_only.split_whitespace().collect();
+use std::{collections::HashMap, env, fs};
 
-    if spring_sets.len() != sets.len() {
-        return false;
+fn expand(src: &str, times: usize, sep: char, out: &mut String) {
+    for i in 0..times {
+        if i > 0 {
+            out.push(sep);
+        }
+        out.push_str(src);
     }
+}
 
-    for (i, spring_count) in spring_sets.iter().enumerate() {
-        if sets[i].len() as u64 != *spring_count {
-            return false;
+fn is_match(condition_records: &[char], at: usize, len: usize) -> bool {
+    let edges_could_be_operational = *condition_records
+        .get(at.checked_sub(1).unwrap_or(usize::MAX))
+        .unwrap_or(&'.')
+        != '#'
+        && *condition_records.get(at + len).unwrap_or(&'.') != '#';
+
+    let springs_could_be_damaged = condition_records
+        .get(at..at + len)
+        .map(|slice| slice.len() == len && slice.iter().all(|c| *c != '.'))
+        .unwrap_or(false);
+
+    edges_could_be_operational && springs_could_be_damaged
+}
+
+//where the row carries on after placing `group` at each possible position from `start`
+fn next_starts(
+    condition_records: &[char],
+    group: usize,
+    remaining_len: usize,
+    start: usize,
+) -> Vec<usize> {
+    let mut result = Vec::new();
+    let last_at = (condition_records.len() + 1).saturating_sub(group + remaining_len);
+    for at in start..last_at {
+        if is_match(condition_records, at, group) {
+            result.push(at + group + 1);
         }
-    }
 
-    true
+        if condition_records[at] == '#' {
+            break;
+        }
+    }
+    result
 }
 
-fn early_pruning(record: &str, spring_set: &str) -> bool {
-    let pass = true;
+//memoised over (group, position), so the work grows with the number of groups
+//times the positions each can take. An explicit stack keeps rows with many
+//groups from running out of call stack
+fn number_of_matches(condition_records: &[char], groups: &[usize]) -> BigUint {
+    let mut remaining_lens = vec![0; groups.len() + 1];
+    for g in (0..groups.len()).rev() {
+        remaining_lens[g] = remaining_lens[g + 1] + groups[g];
+    }
 
-    if spring_set.len() > 0 {
-        let spring_set_counts: Vec<u64> = spring_set
-            .split(',')
-            .map(|s| s.parse::<u64>().unwrap())
-            .collect();
+    let mut cache: HashMap<(usize, usize), BigUint> = HashMap::new();
+    let mut stack = vec![(0, 0)];
+    while let Some(&(g, start)) = stack.last() {
+        if cache.contains_key(&(g, start)) {
+            stack.pop();
+            continue;
+        }
 
-        let sum: u64 = spring_set_counts.iter().sum();
-        let total_possible = record.chars().filter(|c| *c == '?' || *c == '#').count();
-        if sum > total_possible as u64 {
-            return false; //already impossible to fit all springs
-        } 
-
-        let first_unknown = record.find('?');
-        if first_unknown.is_some() {
-            let unknown_idx = first_unknown.unwrap();
-            let (known, _) = record.split_at(unknown_idx);
-            let spring_only = known.replace('.', " ");
-            let groups = spring_only.split_whitespace().collect::<Vec<_>>();
-
-            for (i, g) in groups.iter().enumerate() {
-                //skip last group because it may be incomplete
-                if i >= spring_set_counts.len() || (i == groups.len() - 1 && spring_set_counts[i] >= g.len() as u64) {
-                    continue;
-                }
-                if spring_set_counts[i] != g.len() as u64 {
-                    return false;
-                }
-            }
+        if g == groups.len() {
+            let no_springs_left = condition_records
+                .get(start..)
+                .map(|slice| !slice.contains(&'#'))
+                .unwrap_or(true);
+            cache.insert((g, start), BigUint::from(no_springs_left as u8));
+            stack.pop();
+            continue;
+        }
+
+        let next = next_starts(condition_records, groups[g], remaining_lens[g + 1], start);
+        let missing: Vec<(usize, usize)> = next
+            .iter()
+            .map(|n| (g + 1, *n))
+            .filter(|k| !cache.contains_key(k))
+            .collect();
+        if missing.is_empty() {
+            let ans: BigUint = next.iter().map(|n| &cache[&(g + 1, *n)]).sum();
+            cache.insert((g, start), ans);
+            stack.pop();
+        } else {
+            stack.extend(missing);
         }
     }
+    cache.remove(&(0, 0)).unwrap()
+}
+
+//the row and its groups repeated `unfold` times, with `separator` between the row copies
+fn count_arrangements(record: &str, groups: &[usize], unfold: usize, separator: char) -> BigUint {
+    let mut unfolded = String::new();
+    expand(record, unfold, separator, &mut unfolded);
+    let records: Vec<char> = unfolded.chars().collect();
+    let groups = groups.repeat(unfold);
 
-    pass
+    number_of_matches(&records, &groups)
 }
 
-fn get_all_permutations(record: &str, spring_set: &str, wrong_cache: &mut HashSet<String>) -> HashSet<String> {
-    let mut result = HashSet::new();
+//ways to finish a row from each position and group, so any arrangement can be
+//found by its rank without listing the ones before it. '#' sorts before '.',
+//so arrangements that start a group at a position come first
+struct Arrangements {
+    records: Vec<char>,
+    groups: Vec<usize>,
+    counts: Vec<Vec<BigUint>>, //[position][group], past the end is the last row
+}
 
-    if record.contains('?') && wrong_cache.contains(&record.replace('.', " ").replace('#', " ").trim_start().to_string()) {
-        //already known it is wrong
-        return result;
+impl Arrangements {
+    fn new(record: &str, groups: &[usize]) -> Arrangements {
+        let records: Vec<char> = record.chars().collect();
+        let len = records.len();
+        let mut counts = vec![vec![BigUint::zero(); groups.len() + 1]; len + 1];
+        counts[len][groups.len()] = BigUint::from(1u32);
+        let mut arrangements = Arrangements {
+            records,
+            groups: groups.to_vec(),
+            counts,
+        };
+        for pos in (0..len).rev() {
+            for g in 0..=groups.len() {
+                let (dot, group) = arrangements.choices(pos, g);
+                let ways = dot
+                    .map(|next| arrangements.counts[next][g].clone())
+                    .unwrap_or_default()
+                    + group
+                        .map(|next| arrangements.counts[next][g + 1].clone())
+                        .unwrap_or_default();
+                arrangements.counts[pos][g] = ways;
+            }
+        }
+        arrangements
     }
 
-    if !record.contains('?') || !early_pruning(record, spring_set) {
-        if record.contains('?') {
-            //this sub-branch is already wrong, no need to calc next time
-            wrong_cache.insert(record.replace('.', " ").replace('#', " ").trim_start().to_string());
-        }
-        return result;
+    //where the row carries on after a '.' or after group g placed at pos
+    fn choices(&self, pos: usize, g: usize) -> (Option<usize>, Option<usize>) {
+        let dot = (self.records[pos] != '#').then_some(pos + 1);
+        let group = self.groups.get(g).and_then(|len| {
+            let end = pos + len;
+            let fits = is_match(&self.records, pos, *len);
+            //is_match already checks the cell after, which becomes the separating '.'
+            fits.then_some(usize::min(end + 1, self.records.len()))
+        });
+        (dot, group)
     }
 
-    let spring = replace_one_question_mark(record.to_string(), true);
-    let non_spring = replace_one_question_mark(record.to_string(), false);
-    let spring_sets = get_all_permutations(&spring, spring_set, wrong_cache);
-    let non_spring_sets = get_all_permutations(&non_spring, spring_set, wrong_cache);
-    if !spring.contains('?') {
-        result.insert(spring);
+    fn total(&self) -> &BigUint {
+        &self.counts[0][0]
     }
-    if !non_spring.contains('?') {
-        result.insert(non_spring);
+
+    //the k-th arrangement in lexicographic order, counting from 0
+    fn nth(&self, k: &BigUint) -> Option<String> {
+        if k >= self.total() {
+            return None;
+        }
+        let mut k = k.clone();
+        let mut result = String::new();
+        let mut pos = 0;
+        let mut g = 0;
+        while pos < self.records.len() {
+            let (dot, group) = self.choices(pos, g);
+            if let Some(next) = group {
+                let ways = &self.counts[next][g + 1];
+                if k < *ways {
+                    result.push_str(&"#".repeat(self.groups[g]));
+                    if next > pos + self.groups[g] {
+                        result.push('.');
+                    }
+                    pos = next;
+                    g += 1;
+                    continue;
+                }
+                k -= ways;
+            }
+            //the counts guarantee a '.' is possible here
+            pos = dot.expect("rank out of range");
+            result.push('.');
+        }
+        Some(result)
     }
 
-    result.extend(spring_sets.iter().map(|s| s.clone()));
-    result.extend(non_spring_sets.iter().map(|s| s.clone()));
+    fn iter(&self) -> ArrangementIter<'_> {
+        ArrangementIter {
+            arrangements: self,
+            next: BigUint::zero(),
+        }
+    }
 
-    result
+    fn sample<R: Rng>(&self, rng: &mut R) -> Option<String> {
+        if self.total().is_zero() {
+            return None;
+        }
+        self.nth(&random_below(self.total(), rng))
+    }
 }
 
-fn replace_one_question_mark(record: String, change_to_spring: bool) -> String {
-    let mut result = String::new();
-    let mut done = false;
-    for c in record.chars() {
-        if c == '?' && !done {
-            result.push(if change_to_spring { '#' } else { '.' });
+struct ArrangementIter<'a> {
+    arrangements: &'a Arrangements,
+    next: BigUint,
+}
 
-            done = true;
-        } else {
-            result.push(c);
-        }
+impl Iterator for ArrangementIter<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let result = self.arrangements.nth(&self.next)?;
+        self.next += 1u32;
+        Some(result)
     }
-    result
 }
 
-fn expand(src: &str, times: i32, sep: char, out: &mut String) {
-    out.push_str(src);
-    for _i in 0..times - 1 {
-        out.push(sep);
-        out.push_str(src);
+//uniform in 0..limit, by drawing as many bits as limit has until one is below it
+fn random_below<R: Rng>(limit: &BigUint, rng: &mut R) -> BigUint {
+    let bits = limit.bits();
+    let digits = bits.div_ceil(32) as usize;
+    let top_bits = bits - (digits as u64 - 1) * 32;
+    loop {
+        let mut words: Vec<u32> = (0..digits).map(|_| rng.gen()).collect();
+        if let Some(top) = words.last_mut() {
+            *top &= (u64::pow(2, top_bits as u32) - 1) as u32;
+        }
+        let candidate = BigUint::new(words);
+        if candidate < *limit {
+            return candidate;
+        }
     }
 }
 
-fn is_match(condition_records: &[char], at: usize, len: usize) -> bool {
-    let edges_could_be_operational = *condition_records
-        .get(at.checked_sub(1).unwrap_or(usize::MAX))
-        .unwrap_or(&'.')
-        != '#'
-        && *condition_records.get(at + len).unwrap_or(&'.') != '#';
+type Grid = Vec<Vec<char>>;
 
-    let springs_could_be_damaged = condition_records
-        .get(at..at + len)
-        .map(|slice| slice.len() == len && slice.iter().all(|c| *c != '.'))
-        .unwrap_or(false);
+#[derive(Debug, PartialEq)]
+enum Solution {
+    Unique(Grid),
+    Multiple(Vec<Grid>), //the first two found
+    Contradiction,
+}
 
-    edges_could_be_operational && springs_could_be_damaged
+//a 2D puzzle where every row and column is a spring row with its own groups
+struct Nonogram {
+    rows: Vec<Vec<usize>>,
+    cols: Vec<Vec<usize>>,
 }
 
-fn number_of_matches(
-    cache: &mut HashMap<(usize, usize), usize>,
-    condition_records: &[char],
-    groups: &[usize],
-    start: usize,
-) -> usize {
-    if let Some((group, remaining_groups)) = groups.split_first() {
-        let mut ans = 0;
-        let remaining_len: usize = remaining_groups.iter().sum();
-        for at in start..(condition_records.len() - group - remaining_len + 1) {
-            if is_match(condition_records, at, *group) {
-                let next_at = at + *group + 1;
-                if let Some(cached) = cache.get(&(remaining_len, next_at)) {
-                    ans += cached
-                } else {
-                    let val = number_of_matches(
-                        cache,
-                        condition_records,
-                        remaining_groups,
-                        at + *group + 1,
-                    );
-
-                    cache.insert((remaining_len, next_at), val);
-                    ans += val
+fn has_arrangement(line: &[char], groups: &[usize]) -> bool {
+    !number_of_matches(line, groups).is_zero()
+}
+
+//fills in every unknown cell that is the same in all arrangements, None if there are none
+fn solve_line(line: &[char], groups: &[usize]) -> Option<Vec<char>> {
+    if !has_arrangement(line, groups) {
+        return None;
+    }
+    let mut result = line.to_vec();
+    let mut probe = line.to_vec();
+    for i in 0..line.len() {
+        if line[i] != '?' {
+            continue;
+        }
+        probe[i] = '#';
+        let can_be_spring = has_arrangement(&probe, groups);
+        probe[i] = '.';
+        let can_be_operational = has_arrangement(&probe, groups);
+        probe[i] = '?';
+        match (can_be_spring, can_be_operational) {
+            (true, false) => result[i] = '#',
+            (false, true) => result[i] = '.',
+            _ => {}
+        }
+    }
+    Some(result)
+}
+
+impl Nonogram {
+    //lines of groups like "1,2", with "rows" and "cols" headers and "0" for an empty line
+    fn load(text: &str) -> Result<Nonogram, String> {
+        let mut rows = Vec::new();
+        let mut cols = Vec::new();
+        let mut current: Option<&mut Vec<Vec<usize>>> = None;
+        for line in text.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+            match line {
+                "rows" => current = Some(&mut rows),
+                "cols" => current = Some(&mut cols),
+                _ => {
+                    let groups = line
+                        .split(',')
+                        .map(|g| {
+                            g.trim()
+                                .parse::<usize>()
+                                .map_err(|_| format!("Bad groups: {}", line))
+                        })
+                        .filter(|g| *g != Ok(0))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    current
+                        .as_mut()
+                        .ok_or(format!("Groups before a header: {}", line))?
+                        .push(groups);
                 }
             }
+        }
+        Ok(Nonogram { rows, cols })
+    }
+
+    fn get_col(grid: &Grid, col: usize) -> Vec<char> {
+        grid.iter().map(|r| r[col]).collect()
+    }
 
-            if condition_records[at] == '#' {
-                break;
+    //line solves every row and column until nothing changes, false on a contradiction
+    fn propagate(&self, grid: &mut Grid) -> bool {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, groups) in self.rows.iter().enumerate() {
+                match solve_line(&grid[i], groups) {
+                    None => return false,
+                    Some(line) if line != grid[i] => {
+                        grid[i] = line;
+                        changed = true;
+                    }
+                    _ => {}
+                }
+            }
+            for (j, groups) in self.cols.iter().enumerate() {
+                let col = Nonogram::get_col(grid, j);
+                match solve_line(&col, groups) {
+                    None => return false,
+                    Some(line) if line != col => {
+                        for (i, c) in line.into_iter().enumerate() {
+                            grid[i][j] = c;
+                        }
+                        changed = true;
+                    }
+                    _ => {}
+                }
             }
         }
-        ans
-    } else {
-        condition_records
-            .get(start..)
-            .map(|slice| !slice.iter().any(|c| *c == '#'))
-            .unwrap_or(true) as usize
+        true
     }
-}
 
+    //propagates, then guesses the first unknown cell both ways
+    fn search(&self, mut grid: Grid, limit: usize, solutions: &mut Vec<Grid>) {
+        if solutions.len() >= limit || !self.propagate(&mut grid) {
+            return;
+        }
+        let unknown = (0..grid.len())
+            .flat_map(|i| (0..self.cols.len()).map(move |j| (i, j)))
+            .find(|(i, j)| grid[*i][*j] == '?');
+        match unknown {
+            None => solutions.push(grid),
+            Some((i, j)) => {
+                for guess in ['#', '.'] {
+                    let mut next = grid.clone();
+                    next[i][j] = guess;
+                    self.search(next, limit, solutions);
+                }
+            }
+        }
+    }
+
+    fn solutions(&self, limit: usize) -> Vec<Grid> {
+        let mut solutions = Vec::new();
+        let grid = vec![vec!['?'; self.cols.len()]; self.rows.len()];
+        self.search(grid, limit, &mut solutions);
+        solutions
+    }
+
+    //two solutions are enough to tell, list more with solutions(limit)
+    fn solve(&self) -> Solution {
+        let mut solutions = self.solutions(2);
+        match solutions.len() {
+            0 => Solution::Contradiction,
+            1 => Solution::Unique(solutions.remove(0)),
+            _ => Solution::Multiple(solutions),
+        }
+    }
+}
 
-fn dp(sub_record: &str, remaining_springs: &mut Vec<u64>) -> u64 {
-    let records: Vec<char> = sub_record.chars().collect();
-    let groups = remaining_springs.iter().map(|a| *a as usize).collect::<Vec<_>>();
-    
-    number_of_matches(&mut HashMap::new(), &records, &groups, 0) as u64
+fn print_grid(grid: &Grid) {
+    for row in grid {
+        println!("{}", row.iter().collect::<String>());
+    }
 }
 
+fn parse_line(line: &str) -> (&str, Vec<usize>) {
+    let line_split = line.split_whitespace().collect::<Vec<_>>();
+    let groups = line_split[1]
+        .split(',')
+        .map(|s| s.parse::<usize>().unwrap())
+        .collect();
+    (line_split[0], groups)
+}
 
-fn day12(file: &str, is_p2: bool) -> u64 {
-    let lines = fs::read_to_string(file).expect("cannot read");
-    let total = lines
+fn day12(lines: &str, unfold: usize, separator: char) -> BigUint {
+    lines
         .split('\n')
         .collect::<Vec<_>>()
         .par_iter()
+        .filter(|l| l.len() >= 2)
         .map(|l| {
-            if l.len() < 2 {
-                return 0;
-            }
-            let mut combinations = HashSet::new();
-            let line_split = l.split_whitespace().collect::<Vec<_>>();
-            let mut spring_sets = String::new();
-            let mut record = String::new();
-            if !is_p2 {
-                spring_sets.push_str(line_split[1])
-            } else {
-                expand(line_split[1], 5, ',', &mut spring_sets)
-            };
-            if !is_p2 {
-                record.push_str(line_split[0])
-            } else {
-                expand(line_split[0], 5, '?', &mut record)
-            };
-            if !is_p2 {
-                let mut wrong_cache = HashSet::new();
-                let permutations = get_all_permutations(&record, &spring_sets, &mut wrong_cache);
-                for p in permutations {
-                    if match_records(&spring_sets, p.as_str()) {
-                        combinations.insert(p);
-                    }
-                }
-            } else {
-                let mut spring_set_counts: Vec<u64> = spring_sets
-                    .split(',')
-                    .map(|s| s.parse::<u64>().unwrap())
-                    .collect();
-                return dp(&record, &mut spring_set_counts)
-            }
-            combinations.len() as u64
+            let (record, groups) = parse_line(l);
+            count_arrangements(record, &groups, unfold, separator)
         })
-        .sum();
-    total
+        .sum()
 }
 
 fn main() {
-    let file = "data/day12.txt";
-    println!("p1: {}", day12(file, false));
-    println!("p2: {}", day12(file, true));
+    let lines = fs::read_to_string("data/day12.txt").expect("cannot read");
+    println!("p1: {}", day12(&lines, 1, '?'));
+    println!("p2: {}", day12(&lines, 5, '?'));
+
+    //day12 list|nth|sample <record> <groups> [limit|k|n], day12 nonogram <file> [limit],
+    //or day12 <unfold> [separator]
+    let args: Vec<String> = env::args().collect();
+    if args.len() == 4 && args[1] == "nonogram" {
+        let text = fs::read_to_string(&args[2]).expect("cannot read");
+        let nonogram = Nonogram::load(&text).expect("bad nonogram");
+        let limit = args[3].parse::<usize>().expect(&args[3]);
+        let grids = nonogram.solutions(limit);
+        println!("{} solutions (limit {}):", grids.len(), limit);
+        for grid in grids {
+            print_grid(&grid);
+            println!();
+        }
+    } else if args.len() == 3 && args[1] == "nonogram" {
+        let text = fs::read_to_string(&args[2]).expect("cannot read");
+        let nonogram = Nonogram::load(&text).expect("bad nonogram");
+        match nonogram.solve() {
+            Solution::Unique(grid) => {
+                println!("unique solution:");
+                print_grid(&grid);
+            }
+            Solution::Multiple(grids) => {
+                println!("more than one solution, e.g.:");
+                for grid in grids {
+                    print_grid(&grid);
+                    println!();
+                }
+            }
+            Solution::Contradiction => println!("no solution"),
+        }
+    } else if args.len() > 3 && ["list", "nth", "sample"].contains(&args[1].as_str()) {
+        let line = format!("{} {}", args[2], args[3]);
+        let (record, groups) = parse_line(&line);
+        let arrangements = Arrangements::new(record, &groups);
+        let extra = args.get(4).map(|a| a.as_str());
+        println!("{} arrangements", arrangements.total());
+        match args[1].as_str() {
+            "list" => {
+                let limit = extra.map_or(Ok(20), |l| l.parse::<usize>()).expect("limit");
+                arrangements
+                    .iter()
+                    .take(limit)
+                    .for_each(|a| println!("{}", a));
+            }
+            "nth" => {
+                let k = extra.unwrap_or("0").parse::<BigUint>().expect("k");
+                println!("{:?}", arrangements.nth(&k));
+            }
+            _ => {
+                let n = extra.map_or(Ok(1), |n| n.parse::<usize>()).expect("n");
+                let mut rng = rand::thread_rng();
+                for _ in 0..n {
+                    println!("{:?}", arrangements.sample(&mut rng));
+                }
+            }
+        }
+    } else if args.len() > 1 {
+        let unfold = args[1].parse::<usize>().expect(&args[1]);
+        let separator = args.get(2).and_then(|s| s.chars().next()).unwrap_or('?');
+        println!(
+            "unfold {} with '{}': {}",
+            unfold,
+            separator,
+            day12(&lines, unfold, separator)
+        );
+    }
 }
 
-
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::collections::HashSet;
+
+    //brute force, only kept to check the counts against
+    fn match_records(record: &str, row: &str) -> bool {
+        let spring_sets: Vec<u64> = record
+            .split(',')
+            .map(|s| s.parse::<u64>().unwrap())
+            .collect();
+        let spring_only = row.replace(".", " ");
+        let sets: Vec<&str> = spring_only.split_whitespace().collect();
+
+        if spring_sets.len() != sets.len() {
+            return false;
+        }
+
+        for (i, spring_count) in spring_sets.iter().enumerate() {
+            if sets[i].len() as u64 != *spring_count {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    fn early_pruning(record: &str, spring_set: &str) -> bool {
+        let pass = true;
+
+        if spring_set.len() > 0 {
+            let spring_set_counts: Vec<u64> = spring_set
+                .split(',')
+                .map(|s| s.parse::<u64>().unwrap())
+                .collect();
+
+            let sum: u64 = spring_set_counts.iter().sum();
+            let total_possible = record.chars().filter(|c| *c == '?' || *c == '#').count();
+            if sum > total_possible as u64 {
+                return false; //already impossible to fit all springs
+            }
+
+            let first_unknown = record.find('?');
+            if first_unknown.is_some() {
+                let unknown_idx = first_unknown.unwrap();
+                let (known, _) = record.split_at(unknown_idx);
+                let spring_only = known.replace('.', " ");
+                let groups = spring_only.split_whitespace().collect::<Vec<_>>();
+
+                for (i, g) in groups.iter().enumerate() {
+                    //skip last group because it may be incomplete
+                    if i >= spring_set_counts.len()
+                        || (i == groups.len() - 1 && spring_set_counts[i] >= g.len() as u64)
+                    {
+                        continue;
+                    }
+                    if spring_set_counts[i] != g.len() as u64 {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        pass
+    }
+
+    fn get_all_permutations(
+        record: &str,
+        spring_set: &str,
+        wrong_cache: &mut HashSet<String>,
+    ) -> HashSet<String> {
+        let mut result = HashSet::new();
+
+        //keyed by the whole record, the positions of the '?' alone don't decide it
+        if record.contains('?') && wrong_cache.contains(record) {
+            //already known it is wrong
+            return result;
+        }
+
+        if !record.contains('?') || !early_pruning(record, spring_set) {
+            if record.contains('?') {
+                //this sub-branch is already wrong, no need to calc next time
+                wrong_cache.insert(record.to_string());
+            }
+            return result;
+        }
+
+        let spring = replace_one_question_mark(record.to_string(), true);
+        let non_spring = replace_one_question_mark(record.to_string(), false);
+        let spring_sets = get_all_permutations(&spring, spring_set, wrong_cache);
+        let non_spring_sets = get_all_permutations(&non_spring, spring_set, wrong_cache);
+        if !spring.contains('?') {
+            result.insert(spring);
+        }
+        if !non_spring.contains('?') {
+            result.insert(non_spring);
+        }
+
+        result.extend(spring_sets.iter().map(|s| s.clone()));
+        result.extend(non_spring_sets.iter().map(|s| s.clone()));
+
+        result
+    }
+
+    fn replace_one_question_mark(record: String, change_to_spring: bool) -> String {
+        let mut result = String::new();
+        let mut done = false;
+        for c in record.chars() {
+            if c == '?' && !done {
+                result.push(if change_to_spring { '#' } else { '.' });
+
+                done = true;
+            } else {
+                result.push(c);
+            }
+        }
+        result
+    }
+
+    fn brute_force_arrangements(record: &str, groups: &[usize]) -> Vec<String> {
+        let spring_sets = groups
+            .iter()
+            .map(|g| g.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        if !record.contains('?') {
+            return if match_records(&spring_sets, record) {
+                vec![record.to_string()]
+            } else {
+                vec![]
+            };
+        }
+        let mut wrong_cache = HashSet::new();
+        let mut result: Vec<String> = get_all_permutations(record, &spring_sets, &mut wrong_cache)
+            .into_iter()
+            .filter(|p| match_records(&spring_sets, p))
+            .collect();
+        result.sort();
+        result
+    }
+
+    fn brute_force_count(record: &str, groups: &[usize], unfold: usize, separator: char) -> u64 {
+        let mut unfolded = String::new();
+        expand(record, unfold, separator, &mut unfolded);
+        brute_force_arrangements(&unfolded, &groups.repeat(unfold)).len() as u64
+    }
+
+    fn count(record: &str, groups: &[usize]) -> BigUint {
+        count_arrangements(record, groups, 1, '?')
+    }
+
+    #[test]
+    fn test_count_arrangements() {
+        assert_eq!(count("", &[]), BigUint::from(1u32));
+        assert_eq!(count(".", &[]), BigUint::from(1u32));
+        assert_eq!(count("#", &[]), BigUint::from(0u32));
+        assert_eq!(count("#", &[1]), BigUint::from(1u32));
+        assert_eq!(count("..?..?..", &[1, 1]), BigUint::from(1u32));
+        assert_eq!(count("??.", &[1]), BigUint::from(2u32));
+        assert_eq!(count("???.###", &[1, 1, 3]), BigUint::from(1u32));
+
+        //groups that can't fit
+        assert_eq!(count("#", &[3]), BigUint::from(0u32));
+        assert_eq!(count("??", &[1, 1]), BigUint::from(0u32));
+    }
+
+    #[test]
+    fn test_unfold() {
+        assert_eq!(
+            count_arrangements("???.###", &[1, 1, 3], 5, '?'),
+            BigUint::from(1u32)
+        );
+        assert_eq!(
+            count_arrangements(".??..??...?##.", &[1, 1, 3], 5, '?'),
+            BigUint::from(16384u32)
+        );
+        assert_eq!(
+            count_arrangements("?###????????", &[3, 2, 1], 5, '?'),
+            BigUint::from(506250u32)
+        );
+        //a fixed separator keeps the copies apart
+        assert_eq!(count_arrangements("??", &[1], 3, '.'), BigUint::from(8u32));
+        assert_eq!(
+            count_arrangements("??", &[1, 1], 3, '#'),
+            BigUint::from(0u32)
+        );
+        assert_eq!(count_arrangements("??", &[1], 3, '?'), BigUint::from(20u32));
+        assert_eq!(count_arrangements("?", &[1], 3, '?'), BigUint::from(1u32));
+        //nothing unfolded is an empty row with no groups
+        assert_eq!(count_arrangements("?#", &[1], 0, '?'), BigUint::from(1u32));
+    }
+
+    #[test]
+    fn test_many_groups() {
+        let result = count_arrangements("?#?", &[2], 20000, '.');
+        assert_eq!(result, num::pow(BigUint::from(2u32), 20000));
+        let result = count_arrangements("#", &[1], 20000, '?');
+        assert_eq!(result, BigUint::from(1u32));
+    }
 
     #[test]
-    fn test_dp() {
-        let sub_record = "";
-        let mut remaining = vec![];
-        assert_eq!(dp(sub_record, &mut remaining), 1);
-
-        let sub_record = ".";
-        let mut remaining = vec![];
-        assert_eq!(dp(sub_record, &mut remaining), 1);
-
-        let sub_record = "#";
-        let mut remaining = vec![];
-        assert_eq!(dp(sub_record, &mut remaining), 0);
-        let mut remaining = vec![1];
-        assert_eq!(dp(sub_record, &mut remaining), 1);
-
-        let sub_record = "..?..?..";
-        let mut remaining = vec![1,1];
-        assert_eq!(dp(sub_record, &mut remaining), 1);
-
-        let sub_record = "??.";
-        let mut remaining = vec![1];
-        assert_eq!(dp(sub_record, &mut remaining), 2);
-        let sub_record = "???.###";
-        let mut remaining = vec![1,1,3];
-        assert_eq!(dp(sub_record, &mut remaining), 1);
+    fn test_day12() {
+        let lines = "???.### 1,1,3
+.??..??...?##. 1,1,3
+?#?#?#?#?#?#?#? 1,3,1,6
+????.#...#... 4,1,1
+????.######..#####. 1,6,5
+?###???????? 3,2,1
+";
+        assert_eq!(day12(lines, 1, '?'), BigUint::from(21u32));
+        assert_eq!(day12(lines, 5, '?'), BigUint::from(525152u32));
+    }
+
+    #[test]
+    fn test_large_count() {
+        //50 single springs among 200 unknowns is C(151, 50), well past u64
+        let record = "?".repeat(200);
+        let mut expected = BigUint::from(1u32);
+        for i in 0..50u32 {
+            expected = expected * (151 - i) / (i + 1);
+        }
+        assert!(expected.bits() > 64);
+        assert_eq!(count(&record, &[1; 50]), expected);
+    }
+
+    #[test]
+    fn test_against_brute_force() {
+        let mut rng = StdRng::seed_from_u64(12);
+        for _ in 0..300 {
+            let len = rng.gen_range(1..8);
+            let record: String = (0..len)
+                .map(|_| ['.', '#', '?'][rng.gen_range(0..3)])
+                .collect();
+            let groups: Vec<usize> = (0..rng.gen_range(1..3))
+                .map(|_| rng.gen_range(1..4))
+                .collect();
+            let unfold = rng.gen_range(1..3);
+            let separator = ['.', '#', '?'][rng.gen_range(0..3)];
+            assert_eq!(
+                count_arrangements(&record, &groups, unfold, separator),
+                BigUint::from(brute_force_count(&record, &groups, unfold, separator)),
+                "{} {:?} x{} '{}'",
+                record,
+                groups,
+                unfold,
+                separator
+            );
+        }
+    }
+
+    #[test]
+    fn test_arrangements() {
+        let arrangements = Arrangements::new("?###????????", &[3, 2, 1]);
+        assert_eq!(*arrangements.total(), BigUint::from(10u32));
+        let all: Vec<String> = arrangements.iter().collect();
+        assert_eq!(all.len(), 10);
+        assert_eq!(all[0], ".###.##.#...");
+        assert_eq!(all[9], ".###....##.#");
+        assert!(all.windows(2).all(|w| w[0] < w[1]));
+
+        assert_eq!(arrangements.nth(&BigUint::from(3u32)), Some(all[3].clone()));
+        assert_eq!(arrangements.nth(&BigUint::from(10u32)), None);
+
+        assert_eq!(Arrangements::new("#", &[2]).iter().count(), 0);
+        assert_eq!(
+            Arrangements::new("", &[]).iter().collect::<Vec<_>>(),
+            vec![""]
+        );
+        assert_eq!(
+            Arrangements::new("??", &[]).iter().collect::<Vec<_>>(),
+            vec![".."]
+        );
+    }
+
+    #[test]
+    fn test_arrangements_against_brute_force() {
+        let mut rng = StdRng::seed_from_u64(46);
+        for _ in 0..300 {
+            let len = rng.gen_range(1..12);
+            let record: String = (0..len)
+                .map(|_| ['.', '#', '?'][rng.gen_range(0..3)])
+                .collect();
+            let groups: Vec<usize> = (0..rng.gen_range(1..4))
+                .map(|_| rng.gen_range(1..4))
+                .collect();
+            let arrangements = Arrangements::new(&record, &groups);
+            let expected = brute_force_arrangements(&record, &groups);
+            assert_eq!(arrangements.iter().collect::<Vec<_>>(), expected);
+            assert_eq!(*arrangements.total(), count(&record, &groups));
+        }
+    }
+
+    #[test]
+    fn test_nth_of_huge_row() {
+        //far too many to list, but any rank can be reached directly
+        let record = "?".repeat(200);
+        let arrangements = Arrangements::new(&record, &[1; 50]);
+        let last = arrangements.total() - 1u32;
+        let first = arrangements.nth(&BigUint::zero()).unwrap();
+        assert!(first.starts_with("#.#.#."));
+        let last = arrangements.nth(&last).unwrap();
+        assert!(last.ends_with(".#.#.#"));
+        assert!(last.starts_with(&".".repeat(101)));
+        assert_eq!(last.len(), 200);
+    }
 
+    #[test]
+    fn test_sample() {
+        let mut rng = StdRng::seed_from_u64(460);
+        let arrangements = Arrangements::new("??????", &[2, 1]);
+        let all: Vec<String> = arrangements.iter().collect();
+        assert_eq!(all.len(), 6);
+
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        for _ in 0..6000 {
+            *seen
+                .entry(arrangements.sample(&mut rng).unwrap())
+                .or_insert(0) += 1;
+        }
+        assert_eq!(seen.len(), 6);
+        //each should come up about 1000 times
+        assert!(seen.values().all(|n| (800..1200).contains(n)));
+
+        assert_eq!(Arrangements::new("#", &[2]).sample(&mut rng), None);
+        let limit = BigUint::from(u64::MAX) * 3u32;
+        for _ in 0..100 {
+            assert!(random_below(&limit, &mut rng) < limit);
+        }
+    }
+
+    fn grid(rows: &[&str]) -> Grid {
+        rows.iter().map(|r| r.chars().collect()).collect()
+    }
+
+    fn get_groups(line: &[char]) -> Vec<usize> {
+        line.split(|c| *c != '#')
+            .filter(|g| !g.is_empty())
+            .map(|g| g.len())
+            .collect()
+    }
+
+    fn clues_of(grid: &Grid) -> Nonogram {
+        Nonogram {
+            rows: grid.iter().map(|r| get_groups(r)).collect(),
+            cols: (0..grid[0].len())
+                .map(|j| get_groups(&Nonogram::get_col(grid, j)))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_solve_line() {
+        let line: Vec<char> = "?????".chars().collect();
+        assert_eq!(solve_line(&line, &[4]), Some("?###?".chars().collect()));
+        assert_eq!(solve_line(&line, &[2, 2]), Some("##.##".chars().collect()));
+        assert_eq!(solve_line(&line, &[1]), Some(line.clone()));
+        assert_eq!(solve_line(&line, &[]), Some(".....".chars().collect()));
+        assert_eq!(solve_line(&line, &[3, 2]), None);
+        let line: Vec<char> = "#????".chars().collect();
+        assert_eq!(solve_line(&line, &[2]), Some("##...".chars().collect()));
+    }
+
+    #[test]
+    fn test_nonogram_unique() {
+        let a = grid(&[".###.", "#...#", "#####", "#...#", "#...#"]);
+        let nonogram = clues_of(&a);
+        assert_eq!(
+            nonogram.rows,
+            vec![vec![3], vec![1, 1], vec![5], vec![1, 1], vec![1, 1]]
+        );
+        assert_eq!(nonogram.solve(), Solution::Unique(a));
+
+        let text = "rows\n3\n1,1\n5\n1,1\n1,1\ncols\n4\n1,1\n1,1\n1,1\n4\n";
+        assert_eq!(Nonogram::load(text).unwrap().rows, nonogram.rows);
+        assert_eq!(Nonogram::load(text).unwrap().cols, nonogram.cols);
+    }
+
+    #[test]
+    fn test_nonogram_multiple() {
+        //either diagonal
+        let nonogram = Nonogram::load("rows\n1\n1\ncols\n1\n1\n").unwrap();
+        match nonogram.solve() {
+            Solution::Multiple(grids) => {
+                assert_eq!(grids.len(), 2);
+                assert!(grids.contains(&grid(&["#.", ".#"])));
+                assert!(grids.contains(&grid(&[".#", "#."])));
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(nonogram.solutions(1).len(), 1);
+
+        //every permutation matrix fits, only two are looked for
+        let ones = vec![vec![1]; 12];
+        let nonogram = Nonogram {
+            rows: ones.clone(),
+            cols: ones,
+        };
+        match nonogram.solve() {
+            Solution::Multiple(grids) => assert_eq!(grids.len(), 2),
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(nonogram.solutions(30).len(), 30);
+    }
+
+    #[test]
+    fn test_nonogram_contradiction() {
+        let nonogram = Nonogram::load("rows\n2\n0\ncols\n1\n0\n").unwrap();
+        assert_eq!(nonogram.solve(), Solution::Contradiction);
+        let nonogram = Nonogram::load("rows\n1\ncols\n1\n1\n").unwrap();
+        assert_eq!(nonogram.solve(), Solution::Contradiction);
+
+        assert!(Nonogram::load("1,1\nrows\n").is_err());
+        assert!(Nonogram::load("rows\n1,x\n").is_err());
+    }
+
+    #[test]
+    fn test_nonogram_random() {
+        let mut rng = StdRng::seed_from_u64(47);
+        for _ in 0..50 {
+            let picture: Grid = (0..6)
+                .map(|_| {
+                    (0..5)
+                        .map(|_| if rng.gen_bool(0.5) { '#' } else { '.' })
+                        .collect()
+                })
+                .collect();
+            let nonogram = clues_of(&picture);
+            let solutions = nonogram.solutions(usize::MAX);
+            assert!(solutions.contains(&picture));
+            for s in solutions {
+                assert_eq!(clues_of(&s).rows, nonogram.rows);
+                assert_eq!(clues_of(&s).cols, nonogram.cols);
+            }
+        }
     }
 }
@@ -1,138 +1,126 @@
-use core::panic;
 use std::collections::HashMap;
+use std::env;
 use std::fs;
 
-fn get_line(
-    grid: &HashMap<(u64, u64), char>,
-    row: Option<u64>,
-    col: Option<u64>,
-) -> Option<String> {
-    if row.is_some() && col.is_some() {
-        panic!("Should not happen!");
-    } else if row.is_some() {
-        if !grid.contains_key(&(row.unwrap(), 0)) {
-            return None;
-        }
-        let max_col = get_max(grid, true);
-        let mut out = String::new();
-        for c in 0..max_col + 1 {
-            out.push(*grid.get(&(row.unwrap(), c)).unwrap());
-        }
-
-        return Some(out);
-    } else {
-        if !grid.contains_key(&(0, col.unwrap())) {
-            return None;
-        }
-        let max_row = get_max(grid, false);
-        let mut out = String::new();
-        for r in 0..max_row + 1 {
-            out.push(*grid.get(&(r, col.unwrap())).unwrap());
-        }
-
-        return Some(out);
-    }
+//each row and column of a pattern as a bitmask split into 64 bit words,
+//'#' is a set bit
+struct Pattern {
+    rows: Vec<Vec<u64>>,
+    cols: Vec<Vec<u64>>,
 }
 
-fn find_diff(me: &Vec<String>, other: &Vec<String>) -> u64 {
-    let mut result = 0;
-
-    for i in 0..me.len() {
-        let me_line = &me[i];
-        let other_line = &other[i];
-
-        if me_line.len() == other_line.len() {
-            for (j, c) in me_line.chars().enumerate() {
-                if c != other_line.chars().collect::<Vec<_>>()[j] {
-                    result += 1;
-                }
+impl Pattern {
+    fn new(grid: &HashMap<(u64, u64), char>) -> Pattern {
+        let width = get_max(grid, true) as usize + 1;
+        let height = get_max(grid, false) as usize + 1;
+        let mut rows = vec![vec![0; width.div_ceil(64)]; height];
+        let mut cols = vec![vec![0; height.div_ceil(64)]; width];
+        for ((r, c), ch) in grid {
+            if *ch == '#' {
+                rows[*r as usize][*c as usize / 64] |= 1 << (c % 64);
+                cols[*c as usize][*r as usize / 64] |= 1 << (r % 64);
             }
         }
+        Pattern { rows, cols }
     }
 
-    result
+    fn lines(&self, is_vertical: bool) -> &[Vec<u64>] {
+        if is_vertical {
+            &self.cols
+        } else {
+            &self.rows
+        }
+    }
 }
 
-fn scan(
-    start: u64,
-    is_left_to_right: bool,
-    is_vertical: bool,
-    grid: &HashMap<(u64, u64), char>,
-    is_p2: bool,
-) -> Option<u64> {
-    if start < 1 {
+//cells that differ from their mirror image across the line before `start`,
+//walking in from the left (or top) edge or from the right (or bottom) one,
+//and giving up once more than `limit` are found
+fn count_diff(lines: &[Vec<u64>], start: usize, is_left_to_right: bool, limit: u64) -> Option<u64> {
+    if start < 1 || start >= lines.len() {
         return None;
     }
-    let mut self_lines: Vec<String> = Vec::new();
-    let mut other_lines: Vec<String> = Vec::new();
-
-    for i in 0..start {
-        let mine_idx = if is_left_to_right {
-            start.checked_sub(i + 1)
-        } else {
-            start.checked_add(i)
-        };
-        let other_idx = if is_left_to_right {
-            start.checked_add(i)
-        } else {
-            start.checked_sub(i + 1)
-        };
-
-        if mine_idx.is_none() || other_idx.is_none() {
+    let pairs = start.min(lines.len() - start);
+    let mut result = 0;
+    for i in 0..pairs {
+        let offset = if is_left_to_right { pairs - 1 - i } else { i };
+        let mine = &lines[start - 1 - offset];
+        let other = &lines[start + offset];
+        result += mine
+            .iter()
+            .zip(other)
+            .map(|(a, b)| (a ^ b).count_ones() as u64)
+            .sum::<u64>();
+        if result > limit {
             break;
         }
+    }
+    Some(result)
+}
 
-        let mine = get_line(
-            grid,
-            if is_vertical {
-                None
-            } else {
-                Some(mine_idx.unwrap())
-            },
-            if is_vertical {
-                Some(mine_idx.unwrap())
-            } else {
-                None
-            },
-        );
-        let other = get_line(
-            grid,
-            if is_vertical {
-                None
-            } else {
-                Some(other_idx.unwrap())
-            },
-            if is_vertical {
-                Some(other_idx.unwrap())
-            } else {
-                None
-            },
-        );
+type Cell = (u64, u64);
 
-        if mine.is_none() || other.is_none() {
-            break;
-        }
+#[derive(Debug, PartialEq)]
+struct Reflection {
+    start: u64, //lines before the reflection
+    is_vertical: bool,
+    mismatches: u64,
+}
 
-        self_lines.push(mine.unwrap());
-        other_lines.push(other.unwrap());
+//every possible reflection line, columns first
+fn list_reflections(grid: &HashMap<(u64, u64), char>) -> Vec<Reflection> {
+    let pattern = Pattern::new(grid);
+    let mut result = Vec::new();
+    for is_vertical in [true, false] {
+        let lines = pattern.lines(is_vertical);
+        for start in 1..lines.len() {
+            if let Some(mismatches) = count_diff(lines, start, true, u64::MAX) {
+                result.push(Reflection {
+                    start: start as u64,
+                    is_vertical,
+                    mismatches,
+                });
+            }
+        }
     }
+    result
+}
 
-    if !is_p2 {
-        if self_lines.len() > 0 && other_lines.len() > 0 && self_lines.eq(&other_lines) {
-            Some(start)
-        } else {
-            None
-        }
-    } else {
-        if self_lines.len() > 0
-            && other_lines.len() > 0
-            && find_diff(&self_lines, &other_lines) == 1
-        {
-            Some(start)
-        } else {
-            None
+//pairs of mirrored cells that differ, changing either cell of a pair fixes it
+fn get_smudges(grid: &HashMap<(u64, u64), char>, reflection: &Reflection) -> Vec<(Cell, Cell)> {
+    let pattern = Pattern::new(grid);
+    let lines = pattern.lines(reflection.is_vertical);
+    let start = reflection.start as usize;
+    let mut result = Vec::new();
+    for along in start.saturating_sub(lines.len() - start)..start {
+        let mirrored = 2 * start - 1 - along;
+        for (word, (a, b)) in lines[along].iter().zip(&lines[mirrored]).enumerate() {
+            let mut diff = a ^ b;
+            while diff != 0 {
+                let across = word as u64 * 64 + diff.trailing_zeros() as u64;
+                diff &= diff - 1;
+                if reflection.is_vertical {
+                    result.push(((across, along as u64), (across, mirrored as u64)));
+                } else {
+                    result.push(((along as u64, across), (mirrored as u64, across)));
+                }
+            }
         }
     }
+    result.sort();
+    result
+}
+
+//the first reflection needing exactly k cells changed, and those cells
+fn find_smudged_reflection(
+    grid: &HashMap<(u64, u64), char>,
+    k: u64,
+) -> Option<(Reflection, Vec<(Cell, Cell)>)> {
+    let reflection = list_reflections(grid)
+        .into_iter()
+        .find(|r| r.mismatches == k)?;
+    let smudges = get_smudges(grid, &reflection);
+    Some((reflection, smudges))
 }
 
 fn get_max(grid: &HashMap<(u64, u64), char>, is_horizontal: bool) -> u64 {
@@ -142,44 +130,19 @@ fn get_max(grid: &HashMap<(u64, u64), char>, is_horizontal: bool) -> u64 {
         .unwrap()
 }
 
-fn scan_grid(grid: &HashMap<(u64, u64), char>, is_p2: bool) -> (u64, bool) {
-    let width = get_max(grid, true) + 1;
-    let height = get_max(grid, false) + 1;
-
-    //1. hori left to right
-    for i in 1..width {
-        let scan = scan(i, true, true, grid, is_p2);
-        if scan.is_some() {
-            return (scan.unwrap(), true);
-        }
-    }
-
-    //2. hori right to left
-    for i in width - 1..0 {
-        let scan = scan(i, false, true, grid, is_p2);
-        if scan.is_some() {
-            return (scan.unwrap(), true);
+//the first reflection with exactly `smudges` mismatches, in the same order as
+//list_reflections so the answer matches find_smudged_reflection
+fn scan_grid(grid: &HashMap<(u64, u64), char>, smudges: u64) -> (u64, bool) {
+    match list_reflections(grid)
+        .into_iter()
+        .find(|r| r.mismatches == smudges)
+    {
+        Some(r) => (r.start, r.is_vertical),
+        None => {
+            println!("Nothing found!!!");
+            (0, true)
         }
     }
-
-    //3. vert up to down
-    for i in 1..height {
-        let scan = scan(i, true, false, grid, is_p2);
-        if scan.is_some() {
-            return (scan.unwrap(), false);
-        }
-    }
-
-    //4. vert down to up
-    for i in height - 1..0 {
-        let scan = scan(i, false, false, grid, is_p2);
-        if scan.is_some() {
-            return (scan.unwrap(), false);
-        }
-    }
-
-    println!("Nothing found!!!");
-    (0, true)
 }
 
 fn load_map(lines: &Vec<&str>, grid: &mut HashMap<(u64, u64), char>) {
@@ -199,7 +162,7 @@ fn load_map(lines: &Vec<&str>, grid: &mut HashMap<(u64, u64), char>) {
     }
 }
 
-fn calc_result(file: &str, is_p2: bool) -> u64 {
+fn calc_result(file: &str, smudges: u64) -> u64 {
     let mut result = 0;
 
     let mut cur_lines = Vec::new();
@@ -216,7 +179,7 @@ fn calc_result(file: &str, is_p2: bool) -> u64 {
             //calc, then clear for next round
             //println!("{:?}", cur_lines);
             load_map(&cur_lines, &mut cur_grid);
-            let cur_result = scan_grid(&mut cur_grid, is_p2);
+            let cur_result = scan_grid(&cur_grid, smudges);
 
             if cur_result.1 {
                 //col scan
@@ -233,17 +196,49 @@ fn calc_result(file: &str, is_p2: bool) -> u64 {
 }
 
 fn p2(file: &str) -> u64 {
-    calc_result(file, true)
+    calc_result(file, 1)
 }
 
 fn p1(file: &str) -> u64 {
-    calc_result(file, false)
+    calc_result(file, 0)
+}
+
+fn print_smudges(file: &str, k: u64) {
+    let s = fs::read_to_string(file).expect("");
+    for (i, pattern) in s.split("\n\n").enumerate() {
+        let lines = pattern.split('\n').collect::<Vec<_>>();
+        let mut grid = HashMap::new();
+        load_map(&lines, &mut grid);
+        if grid.is_empty() {
+            continue;
+        }
+        let candidates = list_reflections(&grid)
+            .iter()
+            .map(|r| {
+                let kind = if r.is_vertical { "col" } else { "row" };
+                format!("{} {}: {}", kind, r.start, r.mismatches)
+            })
+            .collect::<Vec<_>>();
+        println!("pattern {}: {}", i + 1, candidates.join(", "));
+        match find_smudged_reflection(&grid, k) {
+            Some((r, cells)) => println!("  {:?}, change one of each: {:?}", r, cells),
+            None => println!("  nothing with {} smudges", k),
+        }
+    }
 }
 
 fn main() {
     let file = "data/day13.txt";
     println!("p1: {}", p1(file));
     println!("p2: {}", p2(file));
+
+    //day13 <smudges>
+    let args: Vec<String> = env::args().collect();
+    if args.len() == 2 {
+        let k = args[1].parse::<u64>().expect(&args[1]);
+        println!("{} smudges: {}", k, calc_result(file, k));
+        print_smudges(file, k);
+    }
 }
 
 #[cfg(test)]
@@ -252,6 +247,21 @@ mod tests {
 
     use super::*;
 
+    //a reflection with exactly `smudges` cells that don't match their mirror image
+    fn scan(
+        start: u64,
+        is_left_to_right: bool,
+        is_vertical: bool,
+        pattern: &Pattern,
+        smudges: u64,
+    ) -> Option<u64> {
+        let lines = pattern.lines(is_vertical);
+        match count_diff(lines, start as usize, is_left_to_right, smudges) {
+            Some(diff) if diff == smudges => Some(start),
+            _ => None,
+        }
+    }
+
     #[test]
     fn test_p1() {
         let file = "data/day13_ex3.txt";
@@ -275,7 +285,7 @@ mod tests {
         let mut grid = HashMap::new();
         load_map(&lines, &mut grid);
 
-        let result = scan_grid(&grid, false);
+        let result = scan_grid(&grid, 0);
         assert_eq!(result.1, true);
         assert_eq!(result.0, 5);
 
@@ -286,7 +296,7 @@ mod tests {
         let mut grid = HashMap::new();
         load_map(&lines, &mut grid);
 
-        let result = scan_grid(&grid, false);
+        let result = scan_grid(&grid, 0);
         assert_eq!(result.1, false);
         assert_eq!(result.0, 4);
     }
@@ -300,7 +310,7 @@ mod tests {
         let mut grid = HashMap::new();
         load_map(&lines, &mut grid);
 
-        let result = scan(7, true, false, &grid, false);
+        let result = scan(7, true, false, &Pattern::new(&grid), 0);
         assert_eq!(result.is_none(), true);
     }
 
@@ -347,23 +357,74 @@ mod tests {
     }
 
     #[test]
-    fn test_get_line() {
+    fn test_pattern() {
         let mut grid = HashMap::new();
 
         grid.insert((0, 0), '.');
         grid.insert((0, 1), '#');
         grid.insert((1, 0), '#');
         grid.insert((1, 1), '.');
+        grid.insert((2, 0), '#');
+        grid.insert((2, 1), '#');
+
+        let pattern = Pattern::new(&grid);
+        assert_eq!(pattern.rows, vec![vec![0b10], vec![0b01], vec![0b11]]);
+        assert_eq!(pattern.cols, vec![vec![0b110], vec![0b101]]);
+        assert_eq!(pattern.lines(true), &pattern.cols[..]);
+    }
 
-        let row = get_line(&grid, Some(0), None).unwrap();
-        assert_eq!(row, ".#");
+    #[test]
+    fn test_count_diff() {
+        let lines = [0b1, 0b11, 0b10, 0b11].map(|l| vec![l]);
+        assert_eq!(count_diff(&lines, 0, true, u64::MAX), None);
+        assert_eq!(count_diff(&lines, 4, true, u64::MAX), None);
+        assert_eq!(count_diff(&lines, 1, true, u64::MAX), Some(1));
+        assert_eq!(count_diff(&lines, 2, true, u64::MAX), Some(2));
+        assert_eq!(count_diff(&lines, 3, false, u64::MAX), Some(1));
+        //both directions see the same pairs
+        assert_eq!(count_diff(&lines, 2, false, u64::MAX), Some(2));
+        //stops early once past the limit
+        assert_eq!(count_diff(&lines, 2, true, 0), Some(1));
+        assert_eq!(count_diff(&lines, 2, false, 0), Some(1));
+    }
 
-        let col = get_line(&grid, None, Some(1)).unwrap();
-        assert_eq!(col, "#.");
+    #[test]
+    fn test_scan_grid_right_edge() {
+        //reflections touching only the right or bottom edge
+        let mut grid = HashMap::new();
+        load_map(&vec!["#.#..", "..#..", "##.##"], &mut grid);
+        assert_eq!(scan_grid(&grid, 0), (4, true));
 
-        assert!(get_line(&grid, Some(3), None).is_none());
+        let mut grid = HashMap::new();
+        load_map(&vec!["#.#", "..#", "##.", "#..", "#.."], &mut grid);
+        assert_eq!(scan_grid(&grid, 0), (4, false));
+    }
 
-        assert!(get_line(&grid, None, Some(3)).is_none());
+    #[test]
+    fn test_wide_pattern() {
+        let row = format!("{}{}", "#.".repeat(30), ".#".repeat(30));
+        let blank = ".".repeat(120);
+        let lines = vec![row.as_str(), row.as_str(), blank.as_str()];
+        let mut grid = HashMap::new();
+        load_map(&lines, &mut grid);
+        assert_eq!(scan_grid(&grid, 0), (60, true));
+        assert_eq!(list_reflections(&grid).len(), 119 + 2);
+
+        //past one word either way, with a smudge in the second word
+        let mut row = "#.".repeat(35);
+        row.push_str(&".#".repeat(35));
+        let mut lines = vec![row.as_str(); 150];
+        let mut smudged = row.clone();
+        smudged.replace_range(100..101, "#");
+        lines[3] = &smudged;
+        let mut grid = HashMap::new();
+        load_map(&lines, &mut grid);
+        let pattern = Pattern::new(&grid);
+        assert_eq!((pattern.rows[0].len(), pattern.cols[0].len()), (3, 3));
+        assert_eq!(scan_grid(&grid, 1), (70, true));
+        let (reflection, cells) = find_smudged_reflection(&grid, 1).unwrap();
+        assert_eq!((reflection.start, reflection.is_vertical), (70, true));
+        assert_eq!(cells, vec![((3, 39), (3, 100))]);
     }
 
     #[test]
@@ -380,7 +441,7 @@ mod tests {
         grid.insert((2, 1), '#');
         grid.insert((2, 2), '.');
 
-        let result = scan(1, true, true, &grid, false);
+        let result = scan(1, true, true, &Pattern::new(&grid), 0);
 
         assert!(result.is_some());
 
@@ -396,7 +457,7 @@ mod tests {
         grid.insert((2, 1), '#');
         grid.insert((2, 2), '#');
 
-        let result = scan(2, false, true, &grid, false);
+        let result = scan(2, false, true, &Pattern::new(&grid), 0);
 
         assert!(result.is_some());
 
@@ -428,7 +489,7 @@ mod tests {
         grid.insert((4, 3), '.');
         grid.insert((4, 4), '#');
 
-        let result = scan(2, true, true, &grid, false);
+        let result = scan(2, true, true, &Pattern::new(&grid), 0);
 
         assert!(result.is_some());
 
@@ -460,7 +521,7 @@ mod tests {
         grid.insert((4, 3), '.');
         grid.insert((4, 4), '#');
 
-        let result = scan(3, false, true, &grid, false);
+        let result = scan(3, false, true, &Pattern::new(&grid), 0);
 
         assert!(result.is_some());
 
@@ -477,7 +538,7 @@ mod tests {
         grid.insert((2, 1), '#');
         grid.insert((2, 2), '.');
 
-        let result = scan(1, true, false, &grid, false);
+        let result = scan(1, true, false, &Pattern::new(&grid), 0);
 
         assert!(result.is_some());
 
@@ -493,7 +554,7 @@ mod tests {
         grid.insert((2, 1), '#');
         grid.insert((2, 2), '#');
 
-        let result = scan(2, false, false, &grid, false);
+        let result = scan(2, false, false, &Pattern::new(&grid), 0);
 
         assert!(result.is_some());
 
@@ -525,7 +586,7 @@ mod tests {
         grid.insert((4, 3), '.');
         grid.insert((4, 4), '#');
 
-        let result = scan(2, true, false, &grid, false);
+        let result = scan(2, true, false, &Pattern::new(&grid), 0);
 
         assert!(result.is_some());
 
@@ -557,8 +618,104 @@ mod tests {
         grid.insert((4, 3), '.');
         grid.insert((4, 4), '#');
 
-        let result = scan(3, false, false, &grid, false);
+        let result = scan(3, false, false, &Pattern::new(&grid), 0);
 
         assert!(result.is_some());
     }
+
+    const FIRST: &str = "#.##..##.
+..#.##.#.
+##......#
+##......#
+..#.##.#.
+..##..##.
+#.#.##.#.";
+
+    const SECOND: &str = "#...##..#
+#....#..#
+..##..###
+#####.##.
+#####.##.
+..##..###
+#....#..#";
+
+    fn load(pattern: &str) -> HashMap<(u64, u64), char> {
+        let mut grid = HashMap::new();
+        load_map(&pattern.split('\n').collect::<Vec<_>>(), &mut grid);
+        grid
+    }
+
+    #[test]
+    fn test_list_reflections() {
+        let grid = load(FIRST);
+        let reflections = list_reflections(&grid);
+        assert_eq!(reflections.len(), 8 + 6);
+        assert!(reflections.contains(&Reflection {
+            start: 5,
+            is_vertical: true,
+            mismatches: 0
+        }));
+        assert!(reflections.contains(&Reflection {
+            start: 3,
+            is_vertical: false,
+            mismatches: 1
+        }));
+        assert_eq!(reflections.iter().filter(|r| r.mismatches == 0).count(), 1);
+    }
+
+    #[test]
+    fn test_find_smudged_reflection() {
+        let grid = load(FIRST);
+        let (reflection, cells) = find_smudged_reflection(&grid, 0).unwrap();
+        assert_eq!((reflection.start, reflection.is_vertical), (5, true));
+        assert!(cells.is_empty());
+
+        let (reflection, cells) = find_smudged_reflection(&grid, 1).unwrap();
+        assert_eq!((reflection.start, reflection.is_vertical), (3, false));
+        assert_eq!(cells, vec![((0, 0), (5, 0))]);
+
+        let grid = load(SECOND);
+        let (reflection, cells) = find_smudged_reflection(&grid, 1).unwrap();
+        assert_eq!((reflection.start, reflection.is_vertical), (1, false));
+        assert_eq!(cells, vec![((0, 4), (1, 4))]);
+        assert_eq!(scan_grid(&grid, 1), (1, false));
+    }
+
+    #[test]
+    fn test_several_smudges() {
+        //two cells off from reflecting after column 2
+        let grid = load("#..#\n.##.\n#...\n.#..");
+        assert_eq!(find_smudged_reflection(&grid, 0), None);
+        let (reflection, cells) = find_smudged_reflection(&grid, 2).unwrap();
+        assert_eq!((reflection.start, reflection.is_vertical), (2, true));
+        assert_eq!(cells, vec![((2, 0), (2, 3)), ((3, 1), (3, 2))]);
+        assert_eq!(scan_grid(&grid, 2), (2, true));
+
+        //fixing one cell of each pair leaves a clean reflection
+        let mut fixed = grid.clone();
+        for (cell, other) in cells {
+            fixed.insert(cell, grid[&other]);
+        }
+        assert_eq!(scan_grid(&fixed, 0), (2, true));
+    }
+
+    #[test]
+    fn test_scan_grid_matches_smudges() {
+        //one smudge away from reflecting after columns 5, 6 and 7, the
+        //total and the printed smudges must come from the same one
+        let grid = load("....##.#");
+        let candidates: Vec<u64> = list_reflections(&grid)
+            .iter()
+            .filter(|r| r.mismatches == 1)
+            .map(|r| r.start)
+            .collect();
+        assert_eq!(candidates, vec![5, 6, 7]);
+        let (reflection, cells) = find_smudged_reflection(&grid, 1).unwrap();
+        assert_eq!(
+            scan_grid(&grid, 1),
+            (reflection.start, reflection.is_vertical)
+        );
+        assert_eq!(scan_grid(&grid, 1), (5, true));
+        assert_eq!(cells, vec![((0, 2), (0, 7))]);
+    }
 }
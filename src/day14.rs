@@ -1,4 +1,4 @@
-use std::{collections::HashMap, fs, time::Instant};
+use std::{collections::HashMap, env, fs, time::Instant};
 
 const ROCK: char = 'O';
 const EMPTY: char = '.';
@@ -94,17 +94,22 @@ fn move_round_to_dir(dir: &Direction, grid: &mut HashMap<(u64, u64), char>) -> b
     any_moved
 }
 
-fn tally_rocks(grid: &HashMap<(u64, u64), char>) -> u64 {
+//load on the given face, a rock scores its distance from the opposite edge
+fn tally_rocks(grid: &HashMap<(u64, u64), char>, face: &Direction) -> u64 {
     let mut total = 0;
     let height = get_max(grid, false) + 1;
     let width = get_max(grid, true) + 1;
 
     for i in 0 .. height {
-        let cur_score = height - i;
         for j in 0 .. width {
             let cell = *grid.get(&(i, j)).unwrap();
             if cell == ROCK {
-                total += cur_score;
+                total += match face {
+                    Direction::NORTH => height - i,
+                    Direction::WEST => width - j,
+                    Direction::SOUTH => i + 1,
+                    Direction::EAST => j + 1,
+                };
             }
         }
     }
@@ -112,6 +117,59 @@ fn tally_rocks(grid: &HashMap<(u64, u64), char>) -> u64 {
     total
 }
 
+fn parse_direction(c: char) -> Result<Direction, String> {
+    match c.to_ascii_uppercase() {
+        'N' => Ok(Direction::NORTH),
+        'W' => Ok(Direction::WEST),
+        'S' => Ok(Direction::SOUTH),
+        'E' => Ok(Direction::EAST),
+        _ => Err(format!("Unknown direction: {}", c)),
+    }
+}
+
+fn parse_script(script: &str) -> Result<Vec<Direction>, String> {
+    let result = script.trim().chars().map(parse_direction).collect::<Result<Vec<_>, _>>()?;
+    if result.is_empty() {
+        return Err("Empty script".to_string());
+    }
+    Ok(result)
+}
+
+fn run_script_once(script: &[Direction], grid: &mut HashMap<(u64, u64), char>) {
+    for dir in script {
+        move_to_dir(dir, grid);
+    }
+}
+
+//apply the script `runs` times then its first `tail` tilts, skipping whole
+//runs once the grid repeats itself
+fn run_script(
+    script: &[Direction],
+    runs: u64,
+    tail: usize,
+    grid: &mut HashMap<(u64, u64), char>,
+) {
+    let mut seen = HashMap::new();
+    let mut run = 0;
+
+    while run < runs {
+        let snapshot = get_grid_snapshot(grid);
+        if let Some(first) = seen.insert(snapshot, run) {
+            let remaining = (runs - run) % (run - first);
+            for _ in 0 .. remaining {
+                run_script_once(script, grid);
+            }
+            break;
+        }
+        run_script_once(script, grid);
+        run += 1;
+    }
+
+    for dir in &script[.. tail] {
+        move_to_dir(dir, grid);
+    }
+}
+
 fn p1(file: &str) -> u64 {
     let s = fs::read_to_string(file).expect("");
     let lines = s.split('\n').collect::<Vec<_>>();
@@ -120,7 +178,7 @@ fn p1(file: &str) -> u64 {
     load_map(&lines, &mut grid);
 
     move_to_dir(&Direction::NORTH, &mut grid);
-    tally_rocks(&grid)
+    tally_rocks(&grid, &Direction::NORTH)
 }
 
 fn move_to_dir(dir: &Direction, grid: &mut HashMap<(u64, u64), char>) {
@@ -144,46 +202,21 @@ fn get_grid_snapshot(grid: &HashMap<(u64, u64), char>) -> String {
     out
 }
 
-fn p2(file: &str) -> u64 {
+fn load_file(file: &str) -> HashMap<(u64, u64), char> {
     let s = fs::read_to_string(file).expect("");
     let lines = s.split('\n').collect::<Vec<_>>();
 
     let mut grid = HashMap::new();
     load_map(&lines, &mut grid);
+    grid
+}
 
-    const CYCLES: u64 = 300;
-    let mut cycle_found = false;
-    let mut cycle_found_begin = 0;
-    let mut cycle_found_last = 0;
-    let mut snapshots = HashMap::new();
-    let mut cycle_scores = HashMap::new();
-    for _i in 0 .. CYCLES {
-        move_to_dir(&Direction::NORTH, &mut grid);
-        move_to_dir(&Direction::WEST, &mut grid);
-        move_to_dir(&Direction::SOUTH, &mut grid);
-        move_to_dir(&Direction::EAST, &mut grid);
-
-        let score = tally_rocks(&grid);
-        cycle_scores.insert(_i, score);
-
-        let snapshot:  String = get_grid_snapshot(&grid);
-        snapshots.entry(snapshot.clone()).and_modify(|c| {
-            println!("CYCLE FOUND: {}", *c);
-            cycle_found = true;
-            cycle_found_begin = *c;
-            cycle_found_last = _i;
-            *c = _i;
-        }).or_insert(_i);
-
-        if cycle_found {
-            break;
-        }
-    }
+fn p2(file: &str) -> u64 {
+    let mut grid = load_file(file);
 
-    println!("cycle: {}, {}", cycle_found_begin, cycle_found_last);
-    let target_cycle = (1_000_000_000 - cycle_found_begin) % (cycle_found_last - cycle_found_begin);
-    *cycle_scores.get(&(cycle_found_begin + target_cycle - 1)).unwrap()
-    
+    let script = parse_script("NWSE").unwrap();
+    run_script(&script, 1_000_000_000, 0, &mut grid);
+    tally_rocks(&grid, &Direction::NORTH)
 }
 
 fn main() {
@@ -201,6 +234,26 @@ fn main() {
 
     let elapsed_time = now.elapsed();
     println!("p2: {}, time: {}", result, elapsed_time.as_millis());
+
+    //day14 <script> <times> [face]
+    let args: Vec<String> = env::args().collect();
+    if args.len() == 3 || args.len() == 4 {
+        let script = parse_script(&args[1]).expect(&args[1]);
+        let times = args[2].parse::<u64>().expect(&args[2]);
+        let face = if args.len() == 4 {
+            let mut chars = args[3].chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => parse_direction(c).expect(&args[3]),
+                _ => panic!("Unknown face: {}", args[3]),
+            }
+        } else {
+            Direction::NORTH
+        };
+
+        let mut grid = load_file(file);
+        run_script(&script, times, 0, &mut grid);
+        println!("{} x {}: {}", args[1], times, tally_rocks(&grid, &face));
+    }
 }
 
 #[cfg(test)]
@@ -228,7 +281,7 @@ mod tests {
 
         let mut grid = HashMap::new();
         load_map(&lines, &mut grid);
-        let result = tally_rocks(&grid);
+        let result = tally_rocks(&grid, &Direction::NORTH);
         assert_eq!(result, 136);
 
     }
@@ -359,4 +412,85 @@ mod tests {
         assert_eq!(result, false);
     }
 
-}
\ No newline at end of file
+    const EXAMPLE: &str = "O....#....
+O.OO#....#
+.....##...
+OO.#O....O
+.O.....O#.
+O.#..O.#.#
+..O..#O..O
+.......O..
+#....###..
+#OO..#....";
+
+    fn load_example() -> HashMap<(u64, u64), char> {
+        let mut grid = HashMap::new();
+        load_map(&EXAMPLE.split('\n').collect::<Vec<_>>(), &mut grid);
+        grid
+    }
+
+    #[test]
+    fn test_tally_faces() {
+        let mut grid = load_example();
+        move_to_dir(&Direction::NORTH, &mut grid);
+        assert_eq!(tally_rocks(&grid, &Direction::NORTH), 136);
+
+        //one rock in the top left corner of a 2x3 grid
+        let mut grid = HashMap::new();
+        load_map(&vec!["O..", "..."], &mut grid);
+        assert_eq!(tally_rocks(&grid, &Direction::NORTH), 2);
+        assert_eq!(tally_rocks(&grid, &Direction::SOUTH), 1);
+        assert_eq!(tally_rocks(&grid, &Direction::WEST), 3);
+        assert_eq!(tally_rocks(&grid, &Direction::EAST), 1);
+    }
+
+    #[test]
+    fn test_parse_script() {
+        assert_eq!(parse_script("NWSE").unwrap().len(), 4);
+        assert_eq!(parse_script("nnesw").unwrap().len(), 5);
+        assert!(parse_script("NX").is_err());
+        assert!(parse_script("").is_err());
+    }
+
+    #[test]
+    fn test_run_script() {
+        let script = parse_script("NWSE").unwrap();
+        let mut grid = load_example();
+        run_script(&script, 1_000_000_000, 0, &mut grid);
+        assert_eq!(tally_rocks(&grid, &Direction::NORTH), 64);
+
+        //partial runs stop mid script
+        let mut grid = load_example();
+        run_script(&script, 0, 1, &mut grid);
+        assert_eq!(tally_rocks(&grid, &Direction::NORTH), 136);
+
+        //run counts near the top of u64
+        let mut grid = load_example();
+        run_script(&script, u64::MAX, 0, &mut grid);
+        let mut expected = load_example();
+        run_script(&script, u64::MAX % 7 + 7, 0, &mut expected);
+        assert_eq!(grid, expected);
+    }
+
+    #[test]
+    fn test_run_script_matches_naive() {
+        let script = parse_script("NNESW").unwrap();
+        let mut naive = load_example();
+        for steps in 1 .. 120 {
+            move_to_dir(&script[(steps - 1) % script.len()], &mut naive);
+
+            let mut grid = load_example();
+            let runs = (steps / script.len()) as u64;
+            run_script(&script, runs, steps % script.len(), &mut grid);
+            assert_eq!(grid, naive, "steps: {}", steps);
+        }
+
+        //a script that settles straight away
+        let script = parse_script("S").unwrap();
+        let mut grid = load_example();
+        run_script(&script, u64::MAX, 0, &mut grid);
+        let mut naive = load_example();
+        move_to_dir(&Direction::SOUTH, &mut naive);
+        assert_eq!(grid, naive);
+    }
+}
@@ -1,8 +1,8 @@
-use std::fs;
+use std::{collections::HashMap, env, fs};
 
 struct Gear {
     adj_num_points: Vec<(usize, usize)>,
-    adj_numbers: Vec<i32>,
+    adj_numbers: Vec<i64>,
 }
 
 fn fill_grid(data: String, grid: &mut Vec<Vec<char>>) {
@@ -93,14 +93,14 @@ fn get_full_num(i: usize, j: usize, grid: &Vec<Vec<char>>) -> (usize, usize) {
     (j, end)
 }
 
-fn parse_num(i: usize, num_st_end: (usize, usize), grid: &Vec<Vec<char>>) -> i32 {
+fn parse_num(i: usize, num_st_end: (usize, usize), grid: &Vec<Vec<char>>) -> i64 {
     let mut num_chars = Vec::new();
     for c in num_st_end.0..num_st_end.1 + 1 {
         num_chars.push(grid[i][c]);
     }
     let num_str: String = num_chars.into_iter().collect();
     //println!("{:?}", num_st_end);
-    num_str.parse::<i32>().expect(&num_str)
+    num_str.parse::<i64>().expect(&num_str)
 }
 
 fn is_first_digit(i: usize, j: usize, grid: &Vec<Vec<char>>) -> bool {
@@ -141,6 +141,184 @@ fn add_gear(i: usize, j: usize, grid: &Vec<Vec<char>>, gears: &mut Vec<Gear>) {
     gears.push(gear);
 }
 
+struct Number {
+    row: usize,
+    start: usize,
+    end: usize,
+    value: i64,
+}
+
+struct Schematic {
+    grid: Vec<Vec<char>>,
+    numbers: HashMap<usize, Number>,
+    number_at: HashMap<(usize, usize), usize>,
+    next_id: usize,
+    part_sum: i64,
+    gear_total: i128, //products of two long numbers don't fit in i64
+}
+
+impl Schematic {
+    fn new(grid: Vec<Vec<char>>) -> Schematic {
+        let mut schematic = Schematic {
+            grid,
+            numbers: HashMap::new(),
+            number_at: HashMap::new(),
+            next_id: 0,
+            part_sum: 0,
+            gear_total: 0,
+        };
+
+        for i in 0..schematic.grid.len() {
+            for j in 0..schematic.grid[i].len() {
+                if schematic.grid[i][j].is_ascii_digit() && is_first_digit(i, j, &schematic.grid) {
+                    let num_st_end = get_full_num(i, j, &schematic.grid);
+                    schematic.insert_number(i, num_st_end);
+                }
+            }
+        }
+
+        let ids: Vec<usize> = schematic.numbers.keys().copied().collect();
+        for id in ids {
+            schematic.part_sum += schematic.part_value(id);
+        }
+        for i in 0..schematic.grid.len() {
+            for j in 0..schematic.grid[i].len() {
+                schematic.gear_total += schematic.gear_ratio(i, j);
+            }
+        }
+        schematic
+    }
+
+    fn insert_number(&mut self, i: usize, num_st_end: (usize, usize)) {
+        let id = self.next_id;
+        self.next_id += 1;
+        for c in num_st_end.0..num_st_end.1 + 1 {
+            self.number_at.insert((i, c), id);
+        }
+        self.numbers.insert(
+            id,
+            Number {
+                row: i,
+                start: num_st_end.0,
+                end: num_st_end.1,
+                value: parse_num(i, num_st_end, &self.grid),
+            },
+        );
+    }
+
+    fn remove_number(&mut self, id: usize) {
+        let n = self.numbers.remove(&id).unwrap();
+        for c in n.start..n.end + 1 {
+            self.number_at.remove(&(n.row, c));
+        }
+    }
+
+    //all cells touching the number, including the number itself
+    fn number_area(&self, id: usize) -> Vec<(usize, usize)> {
+        let n = &self.numbers[&id];
+        let mut result = Vec::new();
+        for c in n.start..n.end + 1 {
+            result.push((n.row, c));
+            result.extend(get_adj_indexes(n.row, c, &self.grid));
+        }
+        result.sort();
+        result.dedup();
+        result
+    }
+
+    fn part_value(&self, id: usize) -> i64 {
+        let n = &self.numbers[&id];
+        for c in n.start..n.end + 1 {
+            if get_adj_symbols(n.row, c, &self.grid).iter().any(is_symbol) {
+                return n.value;
+            }
+        }
+        0
+    }
+
+    fn gear_ratio(&self, i: usize, j: usize) -> i128 {
+        if !is_gear(self.grid[i][j]) {
+            return 0;
+        }
+        let mut adj_ids: Vec<usize> = get_adj_indexes(i, j, &self.grid)
+            .iter()
+            .filter_map(|p| self.number_at.get(p).copied())
+            .collect();
+        adj_ids.sort();
+        adj_ids.dedup();
+        if adj_ids.len() != 2 {
+            return 0;
+        }
+        self.numbers[&adj_ids[0]].value as i128 * self.numbers[&adj_ids[1]].value as i128
+    }
+
+    fn ids_near(&self, i: usize, j: usize) -> Vec<usize> {
+        let mut ids: Vec<usize> = get_adj_indexes(i, j, &self.grid)
+            .iter()
+            .chain([(i, j)].iter())
+            .filter_map(|p| self.number_at.get(p).copied())
+            .collect();
+        ids.sort();
+        ids.dedup();
+        ids
+    }
+
+    //a changed cell can only affect numbers touching it and gears touching those numbers,
+    //so only that area is taken out of the totals and put back after the edit
+    fn set_cell(&mut self, i: usize, j: usize, c: char) {
+        assert!(
+            i < self.grid.len() && j < self.grid[i].len(),
+            "cell out of range"
+        );
+        if self.grid[i][j] == c {
+            return;
+        }
+
+        let old_ids = self.ids_near(i, j);
+        let mut area: Vec<(usize, usize)> = get_adj_indexes(i, j, &self.grid);
+        area.push((i, j));
+        for id in old_ids.iter() {
+            area.extend(self.number_area(*id));
+        }
+        area.sort();
+        area.dedup();
+
+        for id in old_ids.iter() {
+            self.part_sum -= self.part_value(*id);
+        }
+        for p in area.iter() {
+            self.gear_total -= self.gear_ratio(p.0, p.1);
+        }
+
+        //numbers on the edited row may have been split or joined
+        for id in old_ids.iter() {
+            if self.numbers[id].row == i {
+                self.remove_number(*id);
+            }
+        }
+        self.grid[i][j] = c;
+        let from = if j >= 1 { j - 1 } else { j };
+        let to = if j < self.grid[i].len() - 1 { j + 1 } else { j };
+        for col in from..to + 1 {
+            if self.grid[i][col].is_ascii_digit() && !self.number_at.contains_key(&(i, col)) {
+                let mut first = col;
+                while !is_first_digit(i, first, &self.grid) {
+                    first -= 1;
+                }
+                let num_st_end = get_full_num(i, first, &self.grid);
+                self.insert_number(i, num_st_end);
+            }
+        }
+
+        for id in self.ids_near(i, j) {
+            self.part_sum += self.part_value(id);
+        }
+        for p in area.iter() {
+            self.gear_total += self.gear_ratio(p.0, p.1);
+        }
+    }
+}
+
 fn main() {
     let data = fs::read_to_string("data/day3.txt").expect("Cannot read file.");
 
@@ -198,11 +376,26 @@ fn main() {
 
     println!("p1: {}", p1_result);
     println!("p2: {}", p2_result);
+
+    //edits given as "row,col,char" arguments, totals are updated after each one
+    let mut schematic = Schematic::new(grid);
+    for arg in env::args().skip(1) {
+        let parts: Vec<&str> = arg.split(',').collect();
+        let row = parts[0].parse::<usize>().expect(&arg);
+        let col = parts[1].parse::<usize>().expect(&arg);
+        let c = parts[2].chars().next().expect(&arg);
+        schematic.set_cell(row, col, c);
+        println!(
+            "{}: p1: {} p2: {}",
+            arg, schematic.part_sum, schematic.gear_total
+        );
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
 
     #[test]
     fn test_is_symbol() {
@@ -395,6 +588,13 @@ mod tests {
         fill_grid(data, &mut grid);
 
         assert_eq!(parse_num(0, (0, 2), &grid), 100);
+
+        //edits can join runs into numbers past i32
+        let data = String::from("4671111499");
+        let mut grid: Vec<Vec<char>> = Vec::new();
+        fill_grid(data, &mut grid);
+
+        assert_eq!(parse_num(0, (0, 9), &grid), 4671111499);
     }
 
     #[test]
@@ -437,4 +637,88 @@ mod tests {
         assert!(gears.len() == 1);
         assert!(gears[0].adj_num_points[0] == (0, 0));
     }
+
+    fn example_schematic() -> Schematic {
+        let data = String::from(
+            "467..114..\n\
+            ...*......\n\
+            ..35..633.\n\
+            ......#...\n\
+            617*......\n\
+            .....+.58.\n\
+            ..592.....\n\
+            ......755.\n\
+            ...$.*....\n\
+            .664.598..",
+        );
+        let mut grid: Vec<Vec<char>> = Vec::new();
+        fill_grid(data, &mut grid);
+        Schematic::new(grid)
+    }
+
+    #[test]
+    fn test_schematic_totals() {
+        let schematic = example_schematic();
+        assert_eq!(schematic.part_sum, 4361);
+        assert_eq!(schematic.gear_total, 467835);
+    }
+
+    #[test]
+    fn test_set_cell() {
+        let mut schematic = example_schematic();
+
+        //remove the symbol next to 617, it is no longer a part and the gear is gone
+        schematic.set_cell(4, 3, '.');
+        assert_eq!(schematic.part_sum, 4361 - 617);
+        assert_eq!(schematic.gear_total, 467835);
+
+        //join 467 and 114 into 46711114
+        schematic.set_cell(0, 3, '1');
+        schematic.set_cell(0, 4, '1');
+        assert_eq!(schematic.numbers.len(), 9);
+        assert_eq!(schematic.part_sum, 4361 - 617 - 467 + 46711114);
+        assert_eq!(schematic.gear_total, 46711114 * 35 + 755 * 598);
+
+        //split it into 467 and 1114, both are parts and the first gear now touches three numbers
+        schematic.set_cell(0, 3, '.');
+        assert_eq!(schematic.numbers.len(), 10);
+        assert_eq!(schematic.part_sum, 4361 - 617 + 1114);
+        assert_eq!(schematic.gear_total, 755 * 598);
+
+        //setting the same char does nothing
+        schematic.set_cell(0, 0, '4');
+        assert_eq!(schematic.part_sum, 4361 - 617 + 1114);
+
+        //join everything on the first row into 4671111499
+        schematic.set_cell(0, 3, '1');
+        schematic.set_cell(0, 8, '9');
+        schematic.set_cell(0, 9, '9');
+        assert_eq!(schematic.part_sum, 4361 - 617 - 467 + 4671111499);
+        assert_eq!(schematic.gear_total, 4671111499 * 35 + 755 * 598);
+
+        //a gear between two long numbers goes past i64
+        let mut grid: Vec<Vec<char>> = Vec::new();
+        fill_grid(String::from("9999999999.9999999999"), &mut grid);
+        let mut schematic = Schematic::new(grid);
+        schematic.set_cell(0, 10, '*');
+        assert_eq!(schematic.gear_total, 9999999999 * 9999999999);
+    }
+
+    #[test]
+    fn test_set_cell_matches_full_scan() {
+        let mut rng = StdRng::seed_from_u64(26);
+        let chars = ['.', '.', '*', '#', '1', '5', '9'];
+        let mut schematic = example_schematic();
+
+        for _ in 0..500 {
+            let row = rng.gen_range(0..schematic.grid.len());
+            let col = rng.gen_range(0..schematic.grid[row].len());
+            let c = chars[rng.gen_range(0..chars.len())];
+            schematic.set_cell(row, col, c);
+
+            let full = Schematic::new(schematic.grid.clone());
+            assert_eq!(schematic.part_sum, full.part_sum);
+            assert_eq!(schematic.gear_total, full.gear_total);
+        }
+    }
 }
@@ -1,4 +1,5 @@
-use std::{collections::HashMap, fs};
+use num::{BigUint, Zero};
+use std::{env, fs};
 
 fn get_cards_for_line(line: &str) -> i32 {
     let colon_split: Vec<&str> = line.split(':').collect();
@@ -32,53 +33,103 @@ fn get_point_for_line(line: &str) -> i32 {
     base.pow((result - 1).try_into().unwrap())
 }
 
-fn get_repeat_counts(line_no: i32, repeat_counts: &HashMap<i32, i32>) -> i32 {
-    if !repeat_counts.contains_key(&line_no) {
-        return 1;
+fn get_match_counts(data: &str) -> Vec<usize> {
+    let mut result = Vec::new();
+    for line in data.split('\n') {
+        if line.len() < 4 {
+            continue; //empty
+        }
+        result.push(get_cards_for_line(line) as usize);
     }
-    *repeat_counts.get(&line_no).expect("")
+    result
 }
 
-fn update_repeat_counts(line_no: i32, card_count: i32, repeat_counts: &mut HashMap<i32, i32>) {
-    let first_card = line_no + 1;
-    let last_card = line_no + card_count;
-    for c in first_card..last_card + 1 {
-        if !repeat_counts.contains_key(&c) {
-            repeat_counts.insert(c, 2);
-        } else {
-            let cur_count = repeat_counts.get(&c).expect("");
-            repeat_counts.insert(c, cur_count + 1);
+fn get_copy_counts(match_counts: &[usize]) -> Vec<BigUint> {
+    //difference array: copies won by a card are added at the next card and
+    //removed again after the last card it wins, so each card is visited once
+    let card_count = match_counts.len();
+    let mut added: Vec<BigUint> = vec![BigUint::zero(); card_count + 1];
+    let mut removed: Vec<BigUint> = vec![BigUint::zero(); card_count + 1];
+    let mut won = BigUint::zero();
+    let mut result = Vec::with_capacity(card_count);
+
+    for (i, matches) in match_counts.iter().enumerate() {
+        won += &added[i];
+        won -= &removed[i];
+        let copies = &won + 1u32;
+        if *matches > 0 && i + 1 < card_count {
+            let last_card = usize::min(i + matches, card_count - 1);
+            added[i + 1] += &copies;
+            removed[last_card + 1] += &copies;
         }
+        result.push(copies);
     }
+    result
 }
 
-fn fill_zero(last_no: i32, repeat_counts: &mut HashMap<i32, i32>) {
-    for i in 1..last_no + 1 {
-        if !repeat_counts.contains_key(&i) {
-            repeat_counts.insert(i, 1);
-        }
-    }
+fn p2(data: String) -> BigUint {
+    get_copy_counts(&get_match_counts(&data)).iter().sum()
 }
 
-fn p2(data: String) -> i32 {
-    let mut repeat_counts: HashMap<i32, i32> = HashMap::new();
-    repeat_counts.insert(1, 1);
+fn get_contributions(match_counts: &[usize]) -> Vec<BigUint> {
+    //copies produced by one copy of each card, directly or further down the cascade,
+    //worked out from the back using suffix sums of (1 + contribution)
+    let card_count = match_counts.len();
+    let mut result: Vec<BigUint> = vec![BigUint::zero(); card_count];
+    let mut suffix_sums: Vec<BigUint> = vec![BigUint::zero(); card_count + 1];
+
+    for i in (0..card_count).rev() {
+        let last_card = usize::min(i + match_counts[i], card_count - 1);
+        result[i] = &suffix_sums[i + 1] - &suffix_sums[last_card + 1];
+        suffix_sums[i] = &suffix_sums[i + 1] + &result[i] + 1u32;
+    }
+    result
+}
 
-    let mut line_no = 0;
+fn get_longest_chain(match_counts: &[usize]) -> Vec<usize> {
+    //longest run of cards where each one wins a copy of the next, as card numbers
+    let card_count = match_counts.len();
+    let mut lengths = vec![1; card_count];
+    let mut next: Vec<Option<usize>> = vec![None; card_count];
 
-    for line in data.split('\n') {
-        if line.len() < 4 {
-            continue; //empty
-        }
-        line_no += 1;
-        let card_count = get_cards_for_line(line);
-        for _count in 1..get_repeat_counts(line_no, &repeat_counts) + 1 {
-            update_repeat_counts(line_no, card_count, &mut repeat_counts);
+    for i in (0..card_count).rev() {
+        let last_card = usize::min(i + match_counts[i], card_count - 1);
+        for j in i + 1..last_card + 1 {
+            if lengths[j] + 1 > lengths[i] {
+                lengths[i] = lengths[j] + 1;
+                next[i] = Some(j);
+            }
         }
     }
 
-    fill_zero(line_no, &mut repeat_counts);
-    repeat_counts.values().sum()
+    let mut result = Vec::new();
+    let mut cur = (0..card_count).max_by_key(|i| (lengths[*i], card_count - i));
+    while let Some(c) = cur {
+        result.push(c + 1);
+        cur = next[c];
+    }
+    result
+}
+
+fn what_if(data: &str, card_no: usize, winning_nums: &str) -> Result<BigUint, String> {
+    //total copies if the winning numbers of one card were different
+    let mut match_counts = get_match_counts(data);
+    if card_no < 1 || card_no > match_counts.len() {
+        return Err(format!(
+            "No such card: {}, cards are 1 to {}",
+            card_no,
+            match_counts.len()
+        ));
+    }
+    let line = data
+        .split('\n')
+        .filter(|l| l.len() >= 4)
+        .nth(card_no - 1)
+        .unwrap();
+    let my_nums = line.split('|').nth(1).unwrap();
+    let new_line = format!("Card {}: {} | {}", card_no, winning_nums, my_nums);
+    match_counts[card_no - 1] = get_cards_for_line(&new_line) as usize;
+    Ok(get_copy_counts(&match_counts).iter().sum())
 }
 
 fn main() {
@@ -93,6 +144,35 @@ fn main() {
     }
 
     println!("p1: {}", p1_result);
+
+    let match_counts = get_match_counts(&data);
+    let copies: Vec<String> = get_copy_counts(&match_counts)
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+    println!("copies per card: {}", copies.join(" "));
+    let contributions = get_contributions(&match_counts);
+    let top = (0..contributions.len())
+        .max_by_key(|i| &contributions[*i])
+        .unwrap();
+    let chain = get_longest_chain(&match_counts);
+    println!(
+        "card {} contributed most copies: {}",
+        top + 1,
+        contributions[top]
+    );
+    println!("longest chain ({} cards): {:?}", chain.len(), chain);
+
+    //what if: day4 <card no> "<winning numbers>"
+    let args: Vec<String> = env::args().collect();
+    if args.len() == 3 {
+        let card_no = args[1].parse::<usize>().expect(&args[1]);
+        match what_if(&data, card_no, &args[2]) {
+            Ok(total) => println!("what if: {}", total),
+            Err(e) => println!("what if: {}", e),
+        }
+    }
+
     println!("p2: {}", p2(data));
 }
 
@@ -152,41 +232,78 @@ mod tests {
     }
 
     #[test]
-    fn test_get_card_counts() {
-        let mut repeat_counts: HashMap<i32, i32> = HashMap::new();
-        repeat_counts.insert(1, 3);
-        assert_eq!(get_repeat_counts(1, &repeat_counts), 3);
-        assert_eq!(get_repeat_counts(10, &repeat_counts), 1);
+    fn test_get_copy_counts() {
+        let result = get_copy_counts(&[4, 2, 2, 1, 0, 0]);
+        let expected: Vec<BigUint> = [1u32, 2, 4, 8, 14, 1].map(BigUint::from).to_vec();
+        assert_eq!(result, expected);
+
+        //wins past the last card are ignored
+        let result = get_copy_counts(&[5, 3]);
+        let expected: Vec<BigUint> = [1u32, 2].map(BigUint::from).to_vec();
+        assert_eq!(result, expected);
     }
 
     #[test]
-    fn test_update_repeat_counts() {
-        let mut repeat_counts: HashMap<i32, i32> = HashMap::new();
-        update_repeat_counts(1, 3, &mut repeat_counts);
-        assert_eq!(get_repeat_counts(2, &repeat_counts), 2);
-        assert_eq!(get_repeat_counts(3, &repeat_counts), 2);
-        assert_eq!(get_repeat_counts(4, &repeat_counts), 2);
-        update_repeat_counts(2, 1, &mut repeat_counts);
-        assert_eq!(get_repeat_counts(3, &repeat_counts), 3);
-        assert_eq!(get_repeat_counts(4, &repeat_counts), 2);
+    fn test_get_copy_counts_large_deck() {
+        //every card wins the next one, so card n has n copies
+        let card_count: u64 = 1_000_000;
+        let result = get_copy_counts(&vec![1; card_count as usize]);
+        let total: BigUint = result.iter().sum();
+        assert_eq!(result[card_count as usize - 1], BigUint::from(card_count));
+        assert_eq!(total, BigUint::from(card_count * (card_count + 1) / 2));
+
+        //every card wins the next two, the copies overflow u64 well before the end
+        let result = get_copy_counts(&vec![2; 200]);
+        assert_eq!(result[10], BigUint::from(232u32));
+        assert!(result[199].bits() > 64);
     }
 
     #[test]
-    fn test_p2() {
-        let data = fs::read_to_string("data/day4_ex.txt").expect("Cannot read file.");
-        let result = p2(data);
-        assert_eq!(result, 30);
+    fn test_get_contributions() {
+        let match_counts = [4, 2, 2, 1, 0, 0];
+        let result = get_contributions(&match_counts);
+        let expected: Vec<BigUint> = [14u32, 6, 3, 1, 0, 0].map(BigUint::from).to_vec();
+        assert_eq!(result, expected);
+
+        //every card is either an original or won by exactly one other copy
+        let total: BigUint = result.iter().map(|c| c + 1u32).sum();
+        let copies: BigUint = get_copy_counts(&match_counts).iter().sum();
+        assert_eq!(total, copies);
+    }
+
+    #[test]
+    fn test_get_longest_chain() {
+        assert_eq!(get_longest_chain(&[4, 2, 2, 1, 0, 0]), vec![1, 2, 3, 4, 5]);
+        assert_eq!(get_longest_chain(&[0, 0, 1, 0]), vec![3, 4]);
+        assert_eq!(get_longest_chain(&[0, 0]), vec![1]);
+        assert!(get_longest_chain(&[]).is_empty());
     }
 
     #[test]
-    fn test_fill_zero() {
-        let mut repeat_counts: HashMap<i32, i32> = HashMap::new();
-        update_repeat_counts(1, 3, &mut repeat_counts);
-        assert_eq!(get_repeat_counts(2, &repeat_counts), 2);
-        assert_eq!(get_repeat_counts(3, &repeat_counts), 2);
-        assert_eq!(get_repeat_counts(4, &repeat_counts), 2);
-        fill_zero(6, &mut repeat_counts);
-        assert!(repeat_counts.contains_key(&5));
-        assert!(repeat_counts.contains_key(&6));
+    fn test_what_if() {
+        let data = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53\n\
+            Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19\n\
+            Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1\n\
+            Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83\n\
+            Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36\n\
+            Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11\n";
+        assert_eq!(p2(data.to_string()), BigUint::from(30u32));
+
+        //unchanged numbers give the same total
+        assert_eq!(what_if(data, 1, "41 48 83 86 17"), Ok(BigUint::from(30u32)));
+        //card 1 wins nothing: 1 + 1 + 2 + 4 + 7 + 1
+        assert_eq!(what_if(data, 1, "1 2 3 4 5"), Ok(BigUint::from(16u32)));
+        //card 5 now wins card 6
+        assert_eq!(what_if(data, 5, "88"), Ok(BigUint::from(44u32)));
+        //cards are numbered from 1
+        assert!(what_if(data, 0, "88").is_err());
+        assert!(what_if(data, 7, "88").is_err());
+    }
+
+    #[test]
+    fn test_p2() {
+        let data = fs::read_to_string("data/day4_ex.txt").expect("Cannot read file.");
+        let result = p2(data);
+        assert_eq!(result, BigUint::from(30u32));
     }
 }
@@ -1,6 +1,14 @@
-use rayon::prelude::*;
-use std::{cmp::min, fs};
+use petgraph::algo::{all_simple_paths, is_cyclic_directed};
+use petgraph::graph::{Graph, NodeIndex};
+use std::{
+    cmp::{max, min},
+    collections::HashMap,
+    env, fs,
+};
 
+type Range = (i64, i64); //start, length
+
+#[derive(PartialEq, Eq, Debug, Clone)]
 struct Mapping {
     start: i64,
     dest: i64,
@@ -20,6 +28,29 @@ impl Mapping {
         let offset = v - self.start;
         self.dest + offset
     }
+
+    //splits a range into the mapped overlap and the unmatched parts around it
+    fn map_range(&self, range: Range) -> (Option<Range>, Vec<Range>) {
+        let range_end = range.0 + range.1;
+        let overlap_start = max(range.0, self.start);
+        let overlap_end = min(range_end, self.start + self.length);
+        if overlap_start >= overlap_end {
+            return (None, vec![range]);
+        }
+
+        let mut rest = Vec::new();
+        if range.0 < overlap_start {
+            rest.push((range.0, overlap_start - range.0));
+        }
+        if overlap_end < range_end {
+            rest.push((overlap_end, range_end - overlap_end));
+        }
+        let mapped = (
+            self.get_mapped_value(overlap_start),
+            overlap_end - overlap_start,
+        );
+        (Some(mapped), rest)
+    }
 }
 
 impl MappingList {
@@ -30,6 +61,124 @@ impl MappingList {
         }
         cur
     }
+
+    fn apply_range(&self, range: Range) -> Vec<Range> {
+        let mut cur = vec![range];
+        for l in self.mappings.iter() {
+            let mut next = Vec::new();
+            for r in cur {
+                next.extend(get_mapped_ranges(r, l));
+            }
+            cur = merge_ranges(next);
+        }
+        cur
+    }
+
+    //all layers as one piecewise map over 0..i64::MAX, sorted by start
+    fn compose(&self) -> Vec<Mapping> {
+        let mut composed = vec![Mapping {
+            start: 0,
+            dest: 0,
+            length: i64::MAX,
+        }];
+        for l in self.mappings.iter() {
+            let layer = normalize(l);
+            let mut next = Vec::new();
+            for piece in composed.iter() {
+                for (mapped, src) in get_mapped_pieces((piece.dest, piece.length), &layer) {
+                    next.push(Mapping {
+                        start: piece.start + (src - piece.dest),
+                        dest: mapped.0,
+                        length: mapped.1,
+                    });
+                }
+            }
+            composed = merge_pieces(next);
+        }
+        composed
+    }
+}
+
+//fills the gaps between mappings with identity pieces, earlier mappings win on overlaps
+fn normalize(mappings: &[Mapping]) -> Vec<Mapping> {
+    let mut pieces = Vec::new();
+    let mut uncovered: Vec<Range> = vec![(0, i64::MAX)];
+    for mapping in mappings {
+        let mut rest = Vec::new();
+        for r in uncovered {
+            let (mapped, unmatched) = mapping.map_range(r);
+            if let Some(m) = mapped {
+                pieces.push(Mapping {
+                    start: m.0 - mapping.dest + mapping.start,
+                    dest: m.0,
+                    length: m.1,
+                });
+            }
+            rest.extend(unmatched);
+        }
+        uncovered = rest;
+    }
+    for r in uncovered {
+        pieces.push(Mapping {
+            start: r.0,
+            dest: r.0,
+            length: r.1,
+        });
+    }
+    merge_pieces(pieces)
+}
+
+//maps a range through a normalized layer, keeping the source start of every mapped part
+fn get_mapped_pieces(range: Range, layer: &[Mapping]) -> Vec<(Range, i64)> {
+    let mut result = Vec::new();
+    for mapping in layer {
+        if let (Some(mapped), _) = mapping.map_range(range) {
+            result.push((mapped, mapped.0 - mapping.dest + mapping.start));
+        }
+    }
+    result
+}
+
+//sorts pieces and joins neighbours that continue the same offset
+fn merge_pieces(mut pieces: Vec<Mapping>) -> Vec<Mapping> {
+    pieces.retain(|p| p.length > 0);
+    pieces.sort_by_key(|p| p.start);
+    let mut result: Vec<Mapping> = Vec::new();
+    for p in pieces {
+        match result.last_mut() {
+            Some(last)
+                if last.start + last.length == p.start && last.dest + last.length == p.dest =>
+            {
+                last.length += p.length;
+            }
+            _ => result.push(p),
+        }
+    }
+    result
+}
+
+fn invert(pieces: &[Mapping]) -> Vec<Mapping> {
+    let mut result: Vec<Mapping> = pieces
+        .iter()
+        .map(|p| Mapping {
+            start: p.dest,
+            dest: p.start,
+            length: p.length,
+        })
+        .collect();
+    result.sort_by_key(|p| (p.start, p.dest));
+    result
+}
+
+//every seed range ending up in the location range, using the inverted composed map
+fn get_seeds_for_locations(inverse: &[Mapping], locations: Range) -> Vec<Range> {
+    let mut result = Vec::new();
+    for mapping in inverse {
+        if let (Some(mapped), _) = mapping.map_range(locations) {
+            result.push(mapped);
+        }
+    }
+    merge_ranges(result)
 }
 
 fn load_map(lines: Vec<&str>, map: &mut Vec<Mapping>) {
@@ -59,6 +208,164 @@ fn get_mapped_value(v: i64, mappings: &Vec<Mapping>) -> i64 {
     v
 }
 
+fn get_mapped_ranges(range: Range, mappings: &Vec<Mapping>) -> Vec<Range> {
+    let mut result = Vec::new();
+    let mut pending = vec![range];
+    for mapping in mappings {
+        let mut unmatched = Vec::new();
+        for r in pending {
+            let (mapped, rest) = mapping.map_range(r);
+            if let Some(m) = mapped {
+                result.push(m);
+            }
+            unmatched.extend(rest);
+        }
+        pending = unmatched;
+    }
+    //whatever is left passes through unchanged
+    result.extend(pending);
+    merge_ranges(result)
+}
+
+fn merge_ranges(mut ranges: Vec<Range>) -> Vec<Range> {
+    ranges.retain(|r| r.1 > 0);
+    ranges.sort();
+    let mut result: Vec<Range> = Vec::new();
+    for r in ranges {
+        match result.last_mut() {
+            Some(last) if last.0 + last.1 >= r.0 => {
+                last.1 = max(last.0 + last.1, r.0 + r.1) - last.0;
+            }
+            _ => result.push(r),
+        }
+    }
+    result
+}
+
+struct Almanac {
+    seeds: Vec<i64>,
+    graph: Graph<String, Vec<Mapping>>,
+    categories: HashMap<String, NodeIndex>,
+}
+
+impl Almanac {
+    fn add_category(&mut self, name: &str) -> NodeIndex {
+        if let Some(node) = self.categories.get(name) {
+            return *node;
+        }
+        let node = self.graph.add_node(name.to_string());
+        self.categories.insert(name.to_string(), node);
+        node
+    }
+
+    fn get_category(&self, name: &str) -> Result<NodeIndex, String> {
+        self.categories
+            .get(name)
+            .copied()
+            .ok_or(format!("Unknown category {}", name))
+    }
+
+    //one mapping list for every way of getting from one category to the other
+    fn get_mapping_lists(&self, from: &str, to: &str) -> Result<Vec<MappingList>, String> {
+        let from_node = self.get_category(from)?;
+        let to_node = self.get_category(to)?;
+        if from_node == to_node {
+            return Ok(vec![MappingList {
+                mappings: Vec::new(),
+            }]);
+        }
+
+        let paths: Vec<Vec<NodeIndex>> =
+            all_simple_paths(&self.graph, from_node, to_node, 0, None).collect();
+        if paths.is_empty() {
+            return Err(format!("No maps lead from {} to {}", from, to));
+        }
+
+        let mut result = Vec::new();
+        for path in paths {
+            let mut mappings = Vec::new();
+            for w in path.windows(2) {
+                let edge = self.graph.find_edge(w[0], w[1]).unwrap();
+                mappings.push(self.graph[edge].clone());
+            }
+            result.push(MappingList { mappings });
+        }
+        Ok(result)
+    }
+
+    fn convert(&self, v: i64, from: &str, to: &str) -> Result<Vec<i64>, String> {
+        let mut result: Vec<i64> = self
+            .get_mapping_lists(from, to)?
+            .iter()
+            .map(|l| l.apply(v))
+            .collect();
+        result.sort();
+        result.dedup();
+        Ok(result)
+    }
+
+    fn convert_range(&self, range: Range, from: &str, to: &str) -> Result<Vec<Range>, String> {
+        let result = self
+            .get_mapping_lists(from, to)?
+            .iter()
+            .flat_map(|l| l.apply_range(range))
+            .collect();
+        Ok(merge_ranges(result))
+    }
+}
+
+fn load_almanac(data: &str) -> Result<Almanac, String> {
+    let mut almanac = Almanac {
+        seeds: Vec::new(),
+        graph: Graph::new(),
+        categories: HashMap::new(),
+    };
+    let lines: Vec<&str> = data.split('\n').collect();
+    load_seeds(lines[0], &mut almanac.seeds);
+
+    let mut cur_map: Option<(NodeIndex, NodeIndex, Vec<Mapping>)> = None;
+    for line in lines.iter().skip(1) {
+        let line = line.trim();
+        if line.len() <= 2 {
+            //empty
+            continue;
+        } else if line.chars().next().unwrap().is_alphabetic() {
+            //header: A-to-B map:
+            let names: Vec<&str> = line
+                .strip_suffix(" map:")
+                .ok_or(format!("Bad header: {}", line))?
+                .split("-to-")
+                .collect();
+            if names.len() != 2 {
+                return Err(format!("Bad header: {}", line));
+            }
+            if let Some((from, to, map)) = cur_map.take() {
+                almanac.graph.add_edge(from, to, map);
+            }
+            let from = almanac.add_category(names[0]);
+            let to = almanac.add_category(names[1]);
+            if almanac.graph.contains_edge(from, to) {
+                return Err(format!("Duplicate map: {}", line));
+            }
+            cur_map = Some((from, to, Vec::new()));
+        } else {
+            //number lines
+            match cur_map.as_mut() {
+                Some((_, _, map)) => load_map(vec![line], map),
+                None => return Err(format!("Numbers before any map: {}", line)),
+            }
+        }
+    }
+    if let Some((from, to, map)) = cur_map.take() {
+        almanac.graph.add_edge(from, to, map);
+    }
+
+    if is_cyclic_directed(&almanac.graph) {
+        return Err(String::from("Maps contain a cycle"));
+    }
+    Ok(almanac)
+}
+
 fn load_seeds(line: &str, seeds: &mut Vec<i64>) {
     let nums: Vec<&str> = line.split(":").collect();
     let seed_numbers: Vec<&str> = nums[1].split_whitespace().collect();
@@ -81,69 +388,30 @@ fn get_seed_pairs(seeds: &Vec<i64>) -> Vec<(i64, i64)> {
 
 fn main() {
     let data = fs::read_to_string("data/day5.txt").expect("Cannot read file.");
+    let almanac = load_almanac(&data).expect("Cannot load almanac.");
+    let mapping_lists = almanac
+        .get_mapping_lists("seed", "location")
+        .expect("Cannot convert seeds to locations.");
 
-    let mut seeds: Vec<i64> = Vec::new();
-    let mut seeds_soil: Vec<Mapping> = Vec::new();
-    let mut soil_fertilizer: Vec<Mapping> = Vec::new();
-    let mut fertilizer_water: Vec<Mapping> = Vec::new();
-    let mut water_light: Vec<Mapping> = Vec::new();
-    let mut light_temperature: Vec<Mapping> = Vec::new();
-    let mut temperature_humidity: Vec<Mapping> = Vec::new();
-    let mut humidity_location: Vec<Mapping> = Vec::new();
-
-    let lines: Vec<&str> = data.split('\n').collect();
-    load_seeds(lines[0], &mut seeds);
-    let mut cur_map: &mut Vec<Mapping> = &mut seeds_soil;
-    for line in lines.iter().skip(2) {
-        if line.len() <= 2 {
-            //empty
-            continue;
-        } else if line.chars().next().unwrap().is_alphabetic() {
-            cur_map = match line.trim() {
-                "soil-to-fertilizer map:" => &mut soil_fertilizer,
-                "fertilizer-to-water map:" => &mut fertilizer_water,
-                "water-to-light map:" => &mut water_light,
-                "light-to-temperature map:" => &mut light_temperature,
-                "temperature-to-humidity map:" => &mut temperature_humidity,
-                "humidity-to-location map:" => &mut humidity_location,
-                _ => {
-                    println!("Should not happen or first!");
-                    &mut seeds_soil
-                }
-            };
-        } else {
-            //number lines
-            load_map(vec![line], cur_map);
+    let mut min_seed = i64::MAX;
+    let seed_pairs: Vec<(i64, i64)> = get_seed_pairs(&almanac.seeds);
+    for seed in almanac.seeds.iter() {
+        for location in almanac.convert(*seed, "seed", "location").unwrap() {
+            min_seed = min(min_seed, location);
         }
     }
 
-    assert!(humidity_location.len() > 0);
-    assert!(temperature_humidity.len() > 0);
-    assert!(light_temperature.len() > 0);
-    assert!(water_light.len() > 0);
-    assert!(fertilizer_water.len() > 0);
-    assert!(soil_fertilizer.len() > 0);
-    assert!(seeds_soil.len() > 0);
-
-    let mapping_list = MappingList {
-        mappings: vec![
-            seeds_soil,
-            soil_fertilizer,
-            fertilizer_water,
-            water_light,
-            light_temperature,
-            temperature_humidity,
-            humidity_location,
-        ],
-    };
+    println!("p1: {}", min_seed);
 
-    let mut min_seed = i64::MAX;
-    let seed_pairs: Vec<(i64, i64)> = get_seed_pairs(&seeds);
-    for seed in seeds {
-        min_seed = min(min_seed, mapping_list.apply(seed));
+    let mut inverses = Vec::new();
+    for mapping_list in mapping_lists.iter() {
+        let composed = mapping_list.compose();
+        println!("seed start -> location start, length");
+        for piece in composed.iter() {
+            println!("{} -> {}, {}", piece.start, piece.dest, piece.length);
+        }
+        inverses.push(invert(&composed));
     }
-
-    println!("p1: {}", min_seed);
     /*
         let mut min_seed_pair_val = i64::MAX;
         let mut min_seed_pair = (i64::MAX, i64::MAX);
@@ -171,12 +439,32 @@ fn main() {
         }
     */
     let min_seed = seed_pairs
-        .par_iter() // create a parallel iterator over the seed pairs
-        .flat_map(|p| p.0..p.0 + p.1) // map each pair to a range and flatten the ranges
-        .map(|i| mapping_list.apply(i)) // apply the mapping function to each element
-        .min_by(|x, y| x.cmp(y)) // find the minimum element using the cmp method
+        .iter()
+        .flat_map(|p| almanac.convert_range(*p, "seed", "location").unwrap()) // map each pair to disjoint location ranges
+        .map(|r| r.0) // the start is the lowest location of each range
+        .min()
         .unwrap_or(i64::MAX);
     println!("p2: {}", min_seed);
+    println!(
+        "seeds for location {}: {:?}",
+        min_seed,
+        merge_ranges(
+            inverses
+                .iter()
+                .flat_map(|inverse| get_seeds_for_locations(inverse, (min_seed, 1)))
+                .collect()
+        )
+    );
+
+    //any other conversion: day5 <from category> <to category> <value>
+    let args: Vec<String> = env::args().collect();
+    if args.len() == 4 {
+        let v = args[3].parse::<i64>().expect(&args[3]);
+        match almanac.convert(v, &args[1], &args[2]) {
+            Ok(result) => println!("{} {} is {} {:?}", args[1], v, args[2], result),
+            Err(e) => println!("{}", e),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -210,4 +498,238 @@ mod tests {
         assert_eq!(seeds[2], 55);
         assert_eq!(seeds[3], 13);
     }
+
+    #[test]
+    fn test_map_range() {
+        let mapping = Mapping {
+            start: 98,
+            dest: 50,
+            length: 2,
+        };
+        assert_eq!(mapping.map_range((90, 5)), (None, vec![(90, 5)]));
+        assert_eq!(mapping.map_range((98, 2)), (Some((50, 2)), vec![]));
+        assert_eq!(
+            mapping.map_range((95, 10)),
+            (Some((50, 2)), vec![(95, 3), (100, 5)])
+        );
+        assert_eq!(mapping.map_range((99, 10)), (Some((51, 1)), vec![(100, 9)]));
+    }
+
+    #[test]
+    fn test_get_mapped_ranges() {
+        let lines = vec!["50 98 2", "52 50 48"];
+        let mut mappings: Vec<Mapping> = Vec::new();
+        load_map(lines, &mut mappings);
+
+        assert_eq!(get_mapped_ranges((79, 14), &mappings), vec![(81, 14)]);
+        //0..49 passes through, 50..97 becomes 52..99 and 98..99 becomes 50..51
+        assert_eq!(get_mapped_ranges((0, 105), &mappings), vec![(0, 105)]);
+        assert_eq!(
+            get_mapped_ranges((60, 39), &mappings),
+            vec![(50, 1), (62, 38)]
+        );
+    }
+
+    fn example_mapping_list() -> MappingList {
+        let seeds_soil = vec!["50 98 2", "52 50 48"];
+        let soil_fertilizer = vec!["0 15 37", "37 52 2", "39 0 15"];
+        let fertilizer_water = vec!["49 53 8", "0 11 42", "42 0 7", "57 7 4"];
+        let water_light = vec!["88 18 7", "18 25 70"];
+        let light_temperature = vec!["45 77 23", "81 45 19", "68 64 13"];
+        let temperature_humidity = vec!["0 69 1", "1 0 69"];
+        let humidity_location = vec!["60 56 37", "56 93 4"];
+        let mut mappings = Vec::new();
+        for lines in [
+            seeds_soil,
+            soil_fertilizer,
+            fertilizer_water,
+            water_light,
+            light_temperature,
+            temperature_humidity,
+            humidity_location,
+        ] {
+            let mut map: Vec<Mapping> = Vec::new();
+            load_map(lines, &mut map);
+            mappings.push(map);
+        }
+        MappingList { mappings }
+    }
+
+    #[test]
+    fn test_apply_range() {
+        let mapping_list = example_mapping_list();
+        assert_eq!(mapping_list.apply_range((79, 14))[0].0, 46);
+        assert_eq!(mapping_list.apply_range((55, 13))[0].0, 56);
+
+        //every single seed must land in one of the output ranges
+        let ranges = mapping_list.apply_range((0, 100));
+        for seed in 0..100 {
+            let location = mapping_list.apply(seed);
+            assert!(ranges
+                .iter()
+                .any(|r| r.0 <= location && location < r.0 + r.1));
+        }
+        let total: i64 = ranges.iter().map(|r| r.1).sum();
+        assert_eq!(total, 100);
+
+        //huge ranges are no slower than small ones
+        let ranges = mapping_list.apply_range((0, i64::MAX / 2));
+        assert_eq!(ranges[0].0, 0);
+    }
+
+    #[test]
+    fn test_normalize() {
+        let lines = vec!["50 98 2", "52 50 48"];
+        let mut mappings: Vec<Mapping> = Vec::new();
+        load_map(lines, &mut mappings);
+
+        let result = normalize(&mappings);
+        let starts: Vec<(i64, i64, i64)> =
+            result.iter().map(|p| (p.start, p.dest, p.length)).collect();
+        assert_eq!(
+            starts,
+            vec![
+                (0, 0, 50),
+                (50, 52, 48),
+                (98, 50, 2),
+                (100, 100, i64::MAX - 100)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compose() {
+        let mapping_list = example_mapping_list();
+        let composed = mapping_list.compose();
+
+        //pieces cover everything without gaps
+        assert_eq!(composed[0].start, 0);
+        for w in composed.windows(2) {
+            assert_eq!(w[0].start + w[0].length, w[1].start);
+        }
+        assert_eq!(
+            composed.last().unwrap().start + composed.last().unwrap().length,
+            i64::MAX
+        );
+
+        for seed in 0..200 {
+            assert_eq!(get_mapped_value(seed, &composed), mapping_list.apply(seed));
+        }
+    }
+
+    #[test]
+    fn test_get_seeds_for_locations() {
+        let mapping_list = example_mapping_list();
+        let inverse = invert(&mapping_list.compose());
+
+        assert_eq!(get_seeds_for_locations(&inverse, (35, 1)), vec![(13, 1)]);
+        assert_eq!(get_seeds_for_locations(&inverse, (46, 1)), vec![(82, 1)]);
+
+        //brute force check of a location range
+        let seeds = get_seeds_for_locations(&inverse, (40, 20));
+        for seed in 0..200 {
+            let location = mapping_list.apply(seed);
+            let in_seeds = seeds.iter().any(|r| r.0 <= seed && seed < r.0 + r.1);
+            assert_eq!(in_seeds, (40..60).contains(&location));
+        }
+    }
+
+    const EXAMPLE: &str = "seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4
+";
+
+    #[test]
+    fn test_load_almanac() {
+        let almanac = load_almanac(EXAMPLE).unwrap();
+        assert_eq!(almanac.seeds, vec![79, 14, 55, 13]);
+        assert_eq!(almanac.categories.len(), 8);
+        assert_eq!(almanac.graph.edge_count(), 7);
+
+        assert_eq!(almanac.convert(79, "seed", "location"), Ok(vec![82]));
+        assert_eq!(almanac.convert(79, "seed", "soil"), Ok(vec![81]));
+        assert_eq!(almanac.convert(81, "soil", "location"), Ok(vec![82]));
+        assert_eq!(almanac.convert(74, "water", "temperature"), Ok(vec![71]));
+        assert_eq!(almanac.convert(5, "seed", "seed"), Ok(vec![5]));
+        assert_eq!(
+            almanac.convert_range((79, 14), "seed", "location").unwrap()[0],
+            (46, 10)
+        );
+    }
+
+    #[test]
+    fn test_load_almanac_errors() {
+        let almanac = load_almanac(EXAMPLE).unwrap();
+        assert!(almanac.convert(82, "location", "seed").is_err());
+        assert!(almanac.convert(82, "seed", "colour").is_err());
+
+        let cyclic = "seeds: 1\n\nseed-to-soil map:\n1 2 3\n\nsoil-to-seed map:\n1 2 3\n";
+        assert!(load_almanac(cyclic).is_err());
+
+        let bad_header = "seeds: 1\n\nseed to soil map:\n1 2 3\n";
+        assert!(load_almanac(bad_header).is_err());
+
+        let duplicate = "seeds: 1\n\nseed-to-soil map:\n1 2 3\n\nseed-to-soil map:\n1 2 3\n";
+        assert!(load_almanac(duplicate).is_err());
+    }
+
+    #[test]
+    fn test_branching_almanac() {
+        //seed goes to location through soil or through colour, and also to water
+        let data = "seeds: 1 2
+
+seed-to-soil map:
+10 0 5
+
+soil-to-location map:
+100 10 5
+
+seed-to-colour map:
+20 0 5
+
+colour-to-location map:
+200 20 5
+
+seed-to-water map:
+30 0 5
+";
+        let almanac = load_almanac(data).unwrap();
+        assert_eq!(almanac.convert(1, "seed", "water"), Ok(vec![31]));
+        assert_eq!(almanac.convert(1, "seed", "location"), Ok(vec![101, 201]));
+        assert_eq!(
+            almanac.convert_range((0, 2), "seed", "location"),
+            Ok(vec![(100, 2), (200, 2)])
+        );
+        assert!(almanac.convert(1, "water", "location").is_err());
+    }
 }
@@ -1,54 +1,247 @@
-use std::fs;
+use num::integer::Roots;
+use std::{env, fs, rc::Rc};
+
 struct Race {
     total_time: u64,
     hold_time: u64,
 }
 
 impl Race {
-    fn get_travel_distance(&self) -> u64 {
+    fn get_travel_distance(&self) -> u128 {
         if self.hold_time == 0 || self.total_time == 0 {
             return 0;
         }
 
         let remaining = self.total_time - self.hold_time;
 
-        remaining * self.hold_time
+        remaining as u128 * self.hold_time as u128
+    }
+
+    fn is_winning(&self, record: u64) -> bool {
+        self.get_travel_distance() > record as u128
+    }
+}
+
+trait BoatModel {
+    fn get_travel_distance(&self, race: &Race) -> f64;
+
+    fn is_winning(&self, race: &Race, record: u64) -> bool {
+        self.get_travel_distance(race) > record as f64
+    }
+
+    //numeric search for models without a closed form, assuming the distance
+    //rises with the hold time up to a peak and then falls
+    fn get_winning_interval(&self, total_time: u64, record: u64) -> Option<(u64, u64)> {
+        let race = |hold_time: u64| Race {
+            total_time,
+            hold_time,
+        };
+        let dist = |hold_time: u64| self.get_travel_distance(&race(hold_time));
+
+        let mut lo = 0;
+        let mut hi = total_time;
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if dist(mid) < dist(mid + 1) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        let peak = lo;
+        if !self.is_winning(&race(peak), record) {
+            return None;
+        }
+
+        //first winning hold on the rising side
+        let mut lo = 0;
+        let mut hi = peak;
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.is_winning(&race(mid), record) {
+                hi = mid;
+            } else {
+                lo = mid + 1;
+            }
+        }
+        let first = lo;
+
+        //last winning hold on the falling side
+        let mut lo = peak;
+        let mut hi = total_time;
+        while lo < hi {
+            let mid = hi - (hi - lo) / 2;
+            if self.is_winning(&race(mid), record) {
+                lo = mid;
+            } else {
+                hi = mid - 1;
+            }
+        }
+        Some((first, lo))
+    }
+}
+
+//speed goes up by 1 for every millisecond held
+struct Standard {}
+
+impl BoatModel for Standard {
+    fn get_travel_distance(&self, race: &Race) -> f64 {
+        race.get_travel_distance() as f64
+    }
+
+    fn is_winning(&self, race: &Race, record: u64) -> bool {
+        race.is_winning(record)
+    }
+
+    fn get_winning_interval(&self, total_time: u64, record: u64) -> Option<(u64, u64)> {
+        get_winning_interval(total_time, record)
+    }
+}
+
+//speed goes up by a fixed amount for every millisecond held
+struct Accelerated {
+    speed_per_ms: u64,
+}
+
+impl BoatModel for Accelerated {
+    fn get_travel_distance(&self, race: &Race) -> f64 {
+        race.get_travel_distance() as f64 * self.speed_per_ms as f64
+    }
+
+    //too far to count is further than any u64 record
+    fn is_winning(&self, race: &Race, record: u64) -> bool {
+        race.get_travel_distance()
+            .checked_mul(self.speed_per_ms as u128)
+            .is_none_or(|d| d > record as u128)
+    }
+
+    //speed * d > record is the same as d > record / speed for whole distances
+    fn get_winning_interval(&self, total_time: u64, record: u64) -> Option<(u64, u64)> {
+        if self.speed_per_ms == 0 {
+            return None;
+        }
+        get_winning_interval(total_time, record / self.speed_per_ms)
+    }
+}
+
+//like Accelerated, but the boat cannot go faster than top_speed
+struct SpeedCapped {
+    speed_per_ms: u64,
+    top_speed: u64,
+}
+
+impl BoatModel for SpeedCapped {
+    fn get_travel_distance(&self, race: &Race) -> f64 {
+        let speed = u64::min(
+            race.hold_time.saturating_mul(self.speed_per_ms),
+            self.top_speed,
+        );
+        speed as f64 * (race.total_time - race.hold_time) as f64
+    }
+}
+
+//the boat slows down while moving, losing drag * speed every millisecond
+struct Dragged {
+    speed_per_ms: f64,
+    drag: f64,
+}
+
+impl BoatModel for Dragged {
+    fn get_travel_distance(&self, race: &Race) -> f64 {
+        let speed = race.hold_time as f64 * self.speed_per_ms;
+        let remaining = (race.total_time - race.hold_time) as f64;
+        if self.drag == 0.0 {
+            return speed * remaining;
+        }
+        speed * (1.0 - (-self.drag * remaining).exp()) / self.drag
     }
 }
 
 struct Competition {
-    races: Vec<Race>,
+    total_time: u64,
     cur_record: u64,
     winning_races: u64,
+    model: Rc<dyn BoatModel>,
 }
 
 impl Competition {
     fn run_races(&mut self) {
-        self.winning_races = 0;
-        for r in &self.races {
-            let race_record = r.get_travel_distance();
-            if race_record > self.cur_record {
-                self.winning_races += 1;
-            }
-        }
+        self.winning_races = match self
+            .model
+            .get_winning_interval(self.total_time, self.cur_record)
+        {
+            Some((first, last)) => last - first + 1,
+            None => 0,
+        };
     }
 }
 
+//hold * (total - hold) > record, so the winning holds lie strictly between
+//the roots (total +- sqrt(total^2 - 4 * record)) / 2
+fn get_winning_interval(total_time: u64, record: u64) -> Option<(u64, u64)> {
+    let total = total_time as u128;
+    let disc = (total * total).checked_sub(4 * record as u128)?;
+    let race = |hold_time: u64| Race {
+        total_time,
+        hold_time,
+    };
+
+    //the integer square root is exact, so the guess is at most one off either way
+    let mut first = ((total - disc.sqrt()) / 2) as u64;
+    while first > 0 && race(first - 1).is_winning(record) {
+        first -= 1;
+    }
+    while first <= total_time / 2 && !race(first).is_winning(record) {
+        first += 1;
+    }
+    if first > total_time / 2 {
+        return None;
+    }
+    Some((first, total_time - first))
+}
+
+fn create_model(args: &[String]) -> Result<Rc<dyn BoatModel>, String> {
+    let arg = |i: usize| {
+        args.get(i)
+            .ok_or(format!("Missing argument {} for {}", i, args[0]))
+    };
+    let int_arg = |i: usize| {
+        arg(i).and_then(|a| {
+            a.parse::<u64>()
+                .map_err(|e| format!("Bad number {}: {}", a, e))
+        })
+    };
+    let float_arg = |i: usize| {
+        arg(i).and_then(|a| {
+            a.parse::<f64>()
+                .map_err(|e| format!("Bad number {}: {}", a, e))
+        })
+    };
+    Ok(match args.first().map(|a| a.as_str()) {
+        None => Rc::new(Standard {}),
+        Some("accel") => Rc::new(Accelerated {
+            speed_per_ms: int_arg(1)?,
+        }),
+        Some("cap") => Rc::new(SpeedCapped {
+            speed_per_ms: int_arg(1)?,
+            top_speed: int_arg(2)?,
+        }),
+        Some("drag") => Rc::new(Dragged {
+            speed_per_ms: float_arg(1)?,
+            drag: float_arg(2)?,
+        }),
+        Some(other) => return Err(format!("Unknown model {}", other)),
+    })
+}
+
 fn create_comps(times: Vec<u64>, dists: Vec<u64>) -> Vec<Competition> {
     let mut competitions = Vec::new();
-    for i in 0 .. times.len() {
-        let mut races = Vec::new();
-        for t in 0 .. times[i] + 1 {
-            let race = Race {
-                total_time: times[i],
-                hold_time: t
-            };
-            races.push(race);
-        }
+    for i in 0..times.len() {
         let comp = Competition {
-            races,
+            total_time: times[i],
             cur_record: dists[i],
-            winning_races: 0
+            winning_races: 0,
+            model: Rc::new(Standard {}),
         };
         competitions.push(comp);
     }
@@ -114,9 +307,19 @@ fn main() {
     let data = fs::read_to_string("data/day6.txt").expect("Cannot read file.");
     let comps = parse(&data);
     let comps2 = parse_p2(&data);
+    //boat model: day6 [accel <speed> | cap <speed> <top speed> | drag <speed> <drag>]
+    let args: Vec<String> = env::args().skip(1).collect();
+    let model = match create_model(&args) {
+        Ok(m) => m,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
 
     let mut p1_result = 1;
     for mut c in comps {
+        c.model = model.clone();
         c.run_races();
         p1_result *= c.winning_races;
     }
@@ -125,6 +328,7 @@ fn main() {
 
     let mut p2_result = 1;
     for mut c in comps2 {
+        c.model = model.clone();
         c.run_races();
         p2_result *= c.winning_races;
     }
@@ -183,45 +387,54 @@ mod tests {
     #[test]
     fn test_competition() {
         let mut comp = Competition {
-            races: vec![
-                Race {
-                    total_time: 7,
-                    hold_time: 0,
-                },
-                Race {
-                    total_time: 7,
-                    hold_time: 1,
-                },
-                Race {
-                    total_time: 7,
-                    hold_time: 2,
-                },
-                Race {
-                    total_time: 7,
-                    hold_time: 3,
-                },
-                Race {
-                    total_time: 7,
-                    hold_time: 4,
-                },
-                Race {
-                    total_time: 7,
-                    hold_time: 5,
-                },
-                Race {
-                    total_time: 7,
-                    hold_time: 6,
-                },
-                Race {
-                    total_time: 7,
-                    hold_time: 7,
-                },
-            ],
+            total_time: 7,
             cur_record: 9,
             winning_races: 0,
+            model: Rc::new(Standard {}),
         };
         comp.run_races();
         assert_eq!(comp.winning_races, 4);
+
+        comp.model = Rc::new(Accelerated { speed_per_ms: 2 });
+        comp.run_races();
+        assert_eq!(comp.winning_races, 6);
+    }
+
+    #[test]
+    fn test_get_winning_interval() {
+        assert_eq!(get_winning_interval(7, 9), Some((2, 5)));
+        assert_eq!(get_winning_interval(15, 40), Some((4, 11)));
+        assert_eq!(get_winning_interval(71530, 940200), Some((14, 71516)));
+
+        //holds 2 and 5 travel exactly 10, which does not beat the record
+        assert_eq!(get_winning_interval(7, 10), Some((3, 4)));
+        //holds 10 and 20 travel exactly 200
+        assert_eq!(get_winning_interval(30, 200), Some((11, 19)));
+        //the best hold only matches the record
+        assert_eq!(get_winning_interval(8, 16), None);
+        assert_eq!(get_winning_interval(8, 15), Some((4, 4)));
+        assert_eq!(get_winning_interval(7, 12), None);
+        assert_eq!(get_winning_interval(7, 100), None);
+        assert_eq!(get_winning_interval(0, 0), None);
+        assert_eq!(get_winning_interval(2, 0), Some((1, 1)));
+    }
+
+    #[test]
+    fn test_get_winning_interval_large() {
+        //distances no longer fit in u64
+        let total_time = u64::MAX - 1;
+        let half = total_time / 2;
+        let best = half as u128 * (total_time - half) as u128;
+        let record = u64::MAX;
+        let (first, last) = get_winning_interval(total_time, record).unwrap();
+        assert!(best > record as u128);
+        let race = |hold_time: u64| Race {
+            total_time,
+            hold_time,
+        };
+        assert!(race(first).is_winning(record));
+        assert!(!race(first - 1).is_winning(record));
+        assert_eq!(last, total_time - first);
     }
 
     #[test]
@@ -230,11 +443,7 @@ mod tests {
         let result = parse(&data);
         assert_eq!(result.len(), 3);
         assert_eq!(result[0].cur_record, 9);
-        assert_eq!(result[0].races.len(), 8);
-        assert_eq!(result[0].races[0].hold_time, 0);
-        assert_eq!(result[0].races[0].total_time, 7);
-        assert_eq!(result[0].races[7].hold_time, 7);
-        assert_eq!(result[0].races[7].total_time, 7);
+        assert_eq!(result[0].total_time, 7);
         assert_eq!(result[1].cur_record, 40);
         assert_eq!(result[2].cur_record, 200);
     }
@@ -245,10 +454,116 @@ mod tests {
         let result = parse_p2(&data);
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].cur_record, 940200);
-        assert_eq!(result[0].races.len(),71531);
-        assert_eq!(result[0].races[0].hold_time, 0);
-        assert_eq!(result[0].races[0].total_time, 71530);
-        assert_eq!(result[0].races[71530].hold_time, 71530);
-        assert_eq!(result[0].races[71530].total_time, 71530);
+        assert_eq!(result[0].total_time, 71530);
+    }
+
+    fn brute_force(model: &dyn BoatModel, total_time: u64, record: u64) -> Option<(u64, u64)> {
+        let wins: Vec<u64> = (0..total_time + 1)
+            .filter(|h| {
+                model.is_winning(
+                    &Race {
+                        total_time,
+                        hold_time: *h,
+                    },
+                    record,
+                )
+            })
+            .collect();
+        Some((*wins.first()?, *wins.last()?))
+    }
+
+    #[test]
+    fn test_models() {
+        let models: Vec<Box<dyn BoatModel>> = vec![
+            Box::new(Standard {}),
+            Box::new(Accelerated { speed_per_ms: 3 }),
+            Box::new(SpeedCapped {
+                speed_per_ms: 2,
+                top_speed: 15,
+            }),
+            Box::new(Dragged {
+                speed_per_ms: 1.0,
+                drag: 0.05,
+            }),
+        ];
+
+        for model in models.iter() {
+            for total_time in [0, 1, 7, 15, 30, 101] {
+                for record in [0, 9, 40, 200, 1000] {
+                    assert_eq!(
+                        model.get_winning_interval(total_time, record),
+                        brute_force(model.as_ref(), total_time, record)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_numeric_search() {
+        //the default search must agree with the closed form
+        struct Numeric {}
+        impl BoatModel for Numeric {
+            fn get_travel_distance(&self, race: &Race) -> f64 {
+                race.get_travel_distance() as f64
+            }
+        }
+        assert_eq!(Numeric {}.get_winning_interval(7, 9), Some((2, 5)));
+        assert_eq!(Numeric {}.get_winning_interval(30, 200), Some((11, 19)));
+        assert_eq!(Numeric {}.get_winning_interval(7, 12), None);
+        assert_eq!(
+            Numeric {}.get_winning_interval(71530, 940200),
+            get_winning_interval(71530, 940200)
+        );
+    }
+
+    #[test]
+    fn test_speed_capped() {
+        //top speed is reached after 3ms, holding longer only wastes time
+        let model = SpeedCapped {
+            speed_per_ms: 5,
+            top_speed: 15,
+        };
+        let race = Race {
+            total_time: 10,
+            hold_time: 4,
+        };
+        assert_eq!(model.get_travel_distance(&race), 90.0);
+        assert_eq!(model.get_winning_interval(10, 100), Some((3, 3)));
+        assert_eq!(model.get_winning_interval(10, 105), None);
+
+        //hold * speed would overflow, the cap still applies
+        let model = SpeedCapped {
+            speed_per_ms: u64::MAX,
+            top_speed: 15,
+        };
+        assert_eq!(model.get_travel_distance(&race), 90.0);
+    }
+
+    #[test]
+    fn test_accelerated_overflow() {
+        let model = Accelerated {
+            speed_per_ms: u64::MAX,
+        };
+        let race = Race {
+            total_time: u64::MAX,
+            hold_time: u64::MAX / 2,
+        };
+        assert!(model.is_winning(&race, u64::MAX));
+    }
+
+    #[test]
+    fn test_create_model() {
+        let args = |a: &str| a.split(' ').map(|s| s.to_string()).collect::<Vec<_>>();
+        assert!(create_model(&[]).is_ok());
+        assert!(create_model(&args("accel 3")).is_ok());
+        assert!(create_model(&args("cap 5 15")).is_ok());
+        assert!(create_model(&args("drag 1.5 0.1")).is_ok());
+
+        assert!(create_model(&args("accel 2.5")).is_err());
+        assert!(create_model(&args("cap 5 x")).is_err());
+        assert!(create_model(&args("cap 5")).is_err());
+        assert!(create_model(&args("drag 1 y")).is_err());
+        assert!(create_model(&args("warp 9")).is_err());
     }
 }
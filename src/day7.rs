@@ -1,53 +1,167 @@
-use std::{cmp::Ordering, collections::HashMap, fs};
+use num::{BigInt, BigRational, One, ToPrimitive, Zero};
+use rand::Rng;
+use std::{cmp::Ordering, collections::HashMap, env, fs, rc::Rc, time::Instant};
+
+struct Category {
+    name: String,
+    //group sizes sorted from largest, empty means any grouping
+    groups: Vec<u8>,
+    flush: bool,
+}
 
-struct Card {
-    label: char,
-    p1: bool,
+struct RuleSet {
+    //lowest card first
+    ranking: Vec<char>,
+    wild: Vec<char>,
+    hand_size: usize,
+    suited: bool,
+    //weakest category first
+    categories: Vec<Category>,
 }
 
-impl Card {
-    fn get_value(&self) -> u8 {
-        match self.label {
-            'A' => 14,
-            'K' => 13,
-            'Q' => 12,
-            'J' => {
-                if self.p1 {
-                    11
-                } else {
-                    1
+impl RuleSet {
+    fn standard_categories() -> Vec<Category> {
+        [
+            ("high card", vec![1, 1, 1, 1, 1]),
+            ("one pair", vec![2, 1, 1, 1]),
+            ("two pair", vec![2, 2, 1]),
+            ("three of a kind", vec![3, 1, 1]),
+            ("full house", vec![3, 2]),
+            ("four of a kind", vec![4, 1]),
+            ("five of a kind", vec![5]),
+        ]
+        .into_iter()
+        .map(|(name, groups)| Category {
+            name: name.to_string(),
+            groups,
+            flush: false,
+        })
+        .collect()
+    }
+
+    fn p1() -> RuleSet {
+        RuleSet {
+            ranking: "123456789TJQKA".chars().collect(),
+            wild: Vec::new(),
+            hand_size: 5,
+            suited: false,
+            categories: RuleSet::standard_categories(),
+        }
+    }
+
+    fn p2() -> RuleSet {
+        RuleSet {
+            ranking: "J23456789TQKA".chars().collect(),
+            wild: vec!['J'],
+            hand_size: 5,
+            suited: false,
+            categories: RuleSet::standard_categories(),
+        }
+    }
+
+    //ranking: <cards, lowest first>
+    //wild: <cards, spaces or commas between them are fine>
+    //size: <hand size>
+    //suited: <true/false>
+    //category: <name>: <group sizes, e.g. 3 2, or *> [flush]
+    fn load(data: &str) -> RuleSet {
+        let mut rules = RuleSet {
+            ranking: Vec::new(),
+            wild: Vec::new(),
+            hand_size: 5,
+            suited: false,
+            categories: Vec::new(),
+        };
+        for line in data.lines() {
+            let l: Vec<&str> = line.splitn(2, ':').map(|s| s.trim()).collect();
+            if l.len() < 2 {
+                continue;
+            }
+            match l[0] {
+                "ranking" => rules.ranking = l[1].chars().collect(),
+                "wild" => {
+                    rules.wild = l[1]
+                        .chars()
+                        .filter(|c| !c.is_whitespace() && *c != ',')
+                        .collect()
+                }
+                "size" => rules.hand_size = l[1].parse::<usize>().expect(line),
+                "suited" => rules.suited = l[1].parse::<bool>().expect(line),
+                "category" => {
+                    let (name, spec) = l[1].split_once(':').expect(line);
+                    let mut category = Category {
+                        name: name.trim().to_string(),
+                        groups: Vec::new(),
+                        flush: false,
+                    };
+                    for s in spec.split_whitespace() {
+                        match s {
+                            "flush" => category.flush = true,
+                            "*" => {}
+                            _ => category.groups.push(s.parse::<u8>().expect(line)),
+                        }
+                    }
+                    category.groups.sort_by(|a, b| b.cmp(a));
+                    rules.categories.push(category);
                 }
+                _ => panic!("Unknown rule: {}", line),
+            }
+        }
+        for c in rules.wild.iter() {
+            if !rules.ranking.contains(c) {
+                panic!("Wild card {} is not in the ranking", c);
             }
-            'T' => 10,
-            _ => self.label.to_digit(10).unwrap().try_into().unwrap(),
         }
+        rules
     }
-}
 
-impl PartialEq for Card {
-    fn eq(&self, other: &Self) -> bool {
-        self.label.eq(&other.label)
+    fn get_value(&self, label: char) -> u8 {
+        let pos = self
+            .ranking
+            .iter()
+            .position(|c| *c == label)
+            .expect("Unknown card");
+        (pos + 1).try_into().unwrap()
     }
-}
 
-impl Eq for Card {}
+    fn is_wild(&self, label: char) -> bool {
+        self.wild.contains(&label)
+    }
+}
 
-impl PartialOrd for Card {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.get_value().partial_cmp(&other.get_value())
+impl Category {
+    //the biggest groups take the biggest slots, wild cards fill up the rest
+    fn matches(&self, groups: &[u8], suits: &[char]) -> bool {
+        if self.flush && suits.iter().any(|s| *s != suits[0]) {
+            return false;
+        }
+        if self.groups.is_empty() {
+            return true;
+        }
+        groups.len() <= self.groups.len()
+            && groups.iter().zip(self.groups.iter()).all(|(g, p)| g <= p)
     }
 }
 
-impl Ord for Card {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.get_value().cmp(&other.get_value())
+struct Card {
+    label: char,
+    suit: Option<char>,
+}
+
+impl PartialEq for Card {
+    fn eq(&self, other: &Self) -> bool {
+        self.label.eq(&other.label) && self.suit.eq(&other.suit)
     }
 }
 
+impl Eq for Card {}
+
 struct Hand {
     cards: Vec<Card>,
     bid: u32,
-    p1: bool,
+    rules: Rc<RuleSet>,
+    //hand type followed by one byte per card value, so hands sort by comparing keys
+    key: u128,
 }
 
 impl PartialEq for Hand {
@@ -66,16 +180,66 @@ impl PartialOrd for Hand {
 
 impl Ord for Hand {
     fn cmp(&self, other: &Self) -> Ordering {
-        match self.get_hand_type().cmp(&other.get_hand_type()) {
+        self.key.cmp(&other.key)
+    }
+}
+
+impl Hand {
+    fn new(hand: &str, bid: u32, rules: &Rc<RuleSet>) -> Hand {
+        let chars: Vec<char> = hand.chars().collect();
+        let cards: Vec<Card> = if rules.suited {
+            chars
+                .chunks(2)
+                .map(|c| Card {
+                    label: c[0],
+                    suit: c.get(1).copied(),
+                })
+                .collect()
+        } else {
+            chars
+                .iter()
+                .map(|c| Card {
+                    label: *c,
+                    suit: None,
+                })
+                .collect()
+        };
+        assert_eq!(cards.len(), rules.hand_size, "Wrong hand size: {}", hand);
+        assert!(cards.len() < 16, "Hands are limited to 15 cards");
+        let mut hand = Hand {
+            cards,
+            bid,
+            rules: rules.clone(),
+            key: 0,
+        };
+        hand.key = hand.get_sort_key();
+        hand
+    }
+
+    fn get_sort_key(&self) -> u128 {
+        let mut key = self.classify() as u128;
+        for c in self.cards.iter() {
+            key = key << 8 | self.rules.get_value(c.label) as u128;
+        }
+        key
+    }
+
+    //the old comparison, working everything out again on every call
+    fn cmp_unpacked(&self, other: &Self) -> Ordering {
+        match self.classify().cmp(&other.classify()) {
             Ordering::Equal => {} //same hand, cmp inv cards,
             o => return o,
         }
 
-        self.cards.iter().cmp(other.cards.iter())
+        let values = |h: &Hand| -> Vec<u8> {
+            h.cards
+                .iter()
+                .map(|c| self.rules.get_value(c.label))
+                .collect()
+        };
+        values(self).cmp(&values(other))
     }
-}
 
-impl Hand {
     fn group_cards(&self) -> HashMap<char, u8> {
         let mut gp: HashMap<char, u8> = HashMap::new();
         self.cards.iter().map(|c| c.label).for_each(|c| {
@@ -88,57 +252,51 @@ impl Hand {
         gp
     }
 
-    fn get_card_type_counts(&self) -> u8 {
-        let gp = self.group_cards();
-        let has_j = gp.contains_key(&'J');
-        let true_count = gp.into_iter().count().try_into().unwrap();
-        if self.p1 || true_count == 1 {
-            return true_count;
-        }
-        //p2, handle J
-        if has_j {
-            return true_count - 1;
-        }
-        true_count
+    //sizes of the groups of non-wild cards, largest first
+    fn get_group_sizes(&self) -> Vec<u8> {
+        let mut groups: Vec<u8> = self
+            .group_cards()
+            .into_iter()
+            .filter(|g| !self.rules.is_wild(g.0))
+            .map(|g| g.1)
+            .collect();
+        groups.sort_by(|a, b| b.cmp(a));
+        groups
     }
 
-    fn get_high_card_type_count(&self) -> u8 {
-        let gp = self.group_cards();
-        let j_count: u8 = if !gp.contains_key(&'J') {
-            0
-        } else {
-            *gp.get(&'J').unwrap()
-        };
-        let max = *gp.iter().map(|g| g.1).max().unwrap();
-        if self.p1 || j_count == 0 || j_count == 5 {
-            return max;
-        }
-        let non_j_max = *gp
+    //0 if no category matches, otherwise 1 for the weakest category
+    fn get_hand_type(&self) -> u8 {
+        (self.key >> (8 * self.cards.len())) as u8
+    }
+
+    fn classify(&self) -> u8 {
+        let groups = self.get_group_sizes();
+        let suits: Vec<char> = self
+            .cards
             .iter()
-            .filter(|g| *g.0 != 'J')
-            .map(|g| g.1)
-            .max()
-            .unwrap();
-        non_j_max + j_count
+            .filter(|c| !self.rules.is_wild(c.label))
+            .filter_map(|c| c.suit)
+            .collect();
+        match self
+            .rules
+            .categories
+            .iter()
+            .rposition(|c| c.matches(&groups, &suits))
+        {
+            Some(pos) => (pos + 1).try_into().unwrap(),
+            None => 0,
+        }
     }
 
-    fn get_hand_type(&self) -> u8 {
-        let type_count = self.get_card_type_counts();
-        let highest_count = self.get_high_card_type_count();
-        match (type_count, highest_count) {
-            (1, 5) => 7,
-            (2, 4) => 6,
-            (2, 3) => 5,
-            (3, 3) => 4,
-            (3, 2) => 3,
-            (4, 2) => 2,
-            (5, 1) => 1,
-            _ => 0,
+    fn get_hand_type_name(&self) -> &str {
+        match self.get_hand_type() {
+            0 => "nothing",
+            t => &self.rules.categories[t as usize - 1].name,
         }
     }
 }
 
-fn load_hands(file: &str, p1: bool) -> Vec<Hand> {
+fn load_hands(file: &str, rules: &Rc<RuleSet>) -> Vec<Hand> {
     let data = fs::read_to_string(file).expect("Cannot open file");
     let mut hands: Vec<Hand> = Vec::new();
     for line in data.split('\n') {
@@ -149,662 +307,524 @@ fn load_hands(file: &str, p1: bool) -> Vec<Hand> {
         let hand = l[0];
         let bid = l[1].parse::<u32>().unwrap();
 
-        let mut cards = Vec::new();
-        for c in hand.chars() {
-            cards.push(Card { label: c, p1 })
-        }
-        hands.push(Hand { cards, bid, p1 });
+        hands.push(Hand::new(hand, bid, rules));
     }
     hands
 }
 
-fn main() {
-    let file = "data/day7.txt";
-    let mut hands: Vec<Hand> = load_hands(file, true);
-    let mut p1_result: u32 = 0;
+fn get_winnings(mut hands: Vec<Hand>) -> u32 {
+    let mut result: u32 = 0;
     hands.sort();
-    let mut rank = 1;
-    for h in hands {
-        p1_result += rank * h.bid;
-        rank += 1;
+    for (i, h) in hands.iter().enumerate() {
+        result += (i as u32 + 1) * h.bid;
+    }
+    result
+}
+
+struct Deck {
+    //card text ("A", or "Ah" for suited rules) and how many of it are left
+    counts: Vec<(String, u64)>,
+    with_replacement: bool,
+}
+
+impl Deck {
+    fn get_cards(rules: &RuleSet) -> Vec<String> {
+        //the p1 ranking also accepts '1', but a deck has no such card
+        let mut cards = Vec::new();
+        for label in rules.ranking.iter().filter(|l| **l != '1') {
+            if rules.suited {
+                for suit in "hdcs".chars() {
+                    cards.push(format!("{}{}", label, suit));
+                }
+            } else {
+                cards.push(label.to_string());
+            }
+        }
+        cards
+    }
+
+    //every card equally likely on every draw
+    fn infinite(rules: &RuleSet) -> Deck {
+        Deck {
+            counts: Deck::get_cards(rules).into_iter().map(|c| (c, 1)).collect(),
+            with_replacement: true,
+        }
+    }
+
+    //a real deck with a number of copies of each card, known cards are taken out
+    fn finite(rules: &RuleSet, copies: u64) -> Deck {
+        Deck {
+            counts: Deck::get_cards(rules)
+                .into_iter()
+                .map(|c| (c, copies))
+                .collect(),
+            with_replacement: false,
+        }
+    }
+
+    fn take(&mut self, card: &str) {
+        if self.with_replacement {
+            return;
+        }
+        let count = self
+            .counts
+            .iter_mut()
+            .find(|c| c.0 == card)
+            .expect("Card not in deck");
+        assert!(count.1 > 0, "No {} left in deck", card);
+        count.1 -= 1;
+    }
+}
+
+//splits a partly known hand into cards, None for every unknown card ("?" or "??")
+fn parse_partial_hand(hand: &str, rules: &RuleSet) -> Vec<Option<String>> {
+    let chars: Vec<char> = hand.chars().collect();
+    let size = if rules.suited { 2 } else { 1 };
+    chars
+        .chunks(size)
+        .map(|c| {
+            if c[0] == '?' {
+                None
+            } else {
+                Some(c.iter().collect())
+            }
+        })
+        .collect()
+}
+
+//every way of filling in the unknown cards, with its probability
+fn get_completions(hand: &str, rules: &Rc<RuleSet>, deck: &Deck) -> Vec<(Hand, BigRational)> {
+    let cards = parse_partial_hand(hand, rules);
+    let mut deck = Deck {
+        counts: deck.counts.clone(),
+        with_replacement: deck.with_replacement,
+    };
+    for c in cards.iter().flatten() {
+        deck.take(c);
+    }
+
+    let mut result = Vec::new();
+    let mut cur = Vec::new();
+    complete(
+        &cards,
+        &mut deck,
+        &mut cur,
+        BigRational::one(),
+        rules,
+        &mut result,
+    );
+    result
+}
+
+fn complete(
+    cards: &[Option<String>],
+    deck: &mut Deck,
+    cur: &mut Vec<String>,
+    prob: BigRational,
+    rules: &Rc<RuleSet>,
+    result: &mut Vec<(Hand, BigRational)>,
+) {
+    if cur.len() == cards.len() {
+        result.push((Hand::new(&cur.concat(), 0, rules), prob));
+        return;
+    }
+    if let Some(c) = &cards[cur.len()] {
+        cur.push(c.clone());
+        complete(cards, deck, cur, prob, rules, result);
+        cur.pop();
+        return;
+    }
+
+    let total: u64 = deck.counts.iter().map(|c| c.1).sum();
+    for i in 0..deck.counts.len() {
+        let count = deck.counts[i].1;
+        if count == 0 {
+            continue;
+        }
+        let p = &prob * BigRational::new(BigInt::from(count), BigInt::from(total));
+        cur.push(deck.counts[i].0.clone());
+        if !deck.with_replacement {
+            deck.counts[i].1 -= 1;
+        }
+        complete(cards, deck, cur, p, rules, result);
+        if !deck.with_replacement {
+            deck.counts[i].1 += 1;
+        }
+        cur.pop();
     }
-    println!("p1: {}", p1_result);
+}
+
+//probability of ending up with each hand type, weakest first
+fn get_type_distribution(hand: &str, rules: &Rc<RuleSet>, deck: &Deck) -> Vec<(u8, BigRational)> {
+    let mut dist: HashMap<u8, BigRational> = HashMap::new();
+    for (h, p) in get_completions(hand, rules, deck) {
+        *dist.entry(h.get_hand_type()).or_insert(BigRational::zero()) += p;
+    }
+    let mut result: Vec<(u8, BigRational)> = dist.into_iter().collect();
+    result.sort();
+    result
+}
+
+//rank the hand would get if it joined the (sorted) hands, averaged over all completions
+fn get_expected_rank(hand: &str, rules: &Rc<RuleSet>, deck: &Deck, hands: &[Hand]) -> BigRational {
+    let mut result = BigRational::zero();
+    for (h, p) in get_completions(hand, rules, deck) {
+        let weaker = hands.partition_point(|other| other < &h);
+        result += p * BigRational::from(BigInt::from(weaker + 1));
+    }
+    result
+}
+
+fn print_odds(hand: &str, rules: &Rc<RuleSet>, deck: &Deck, hands: &mut [Hand]) {
+    for (t, p) in get_type_distribution(hand, rules, deck) {
+        let name = match t {
+            0 => "nothing",
+            t => &rules.categories[t as usize - 1].name,
+        };
+        println!("{}: {} ({:.4})", name, p, p.to_f64().unwrap());
+    }
+    hands.sort();
+    let rank = get_expected_rank(hand, rules, deck, hands);
+    println!("expected rank: {:.4}", rank.to_f64().unwrap());
+}
 
-    let mut hands: Vec<Hand> = load_hands(file, false);
-    let mut p2_result: u32 = 0;
+fn bench(count: usize) {
+    let rules = Rc::new(RuleSet::p2());
+    let mut rng = rand::thread_rng();
+    let inputs: Vec<String> = (0..count)
+        .map(|_| {
+            (0..rules.hand_size)
+                .map(|_| rules.ranking[rng.gen_range(0..rules.ranking.len())])
+                .collect()
+        })
+        .collect();
+
+    let start = Instant::now();
+    let mut hands: Vec<Hand> = inputs
+        .iter()
+        .enumerate()
+        .map(|(i, h)| Hand::new(h, i as u32, &rules))
+        .collect();
+    println!("build {} hands: {:?}", count, start.elapsed());
+
+    let mut unpacked: Vec<&Hand> = hands.iter().collect();
+    let start = Instant::now();
+    unpacked.sort_by(|a, b| a.cmp_unpacked(b));
+    println!("sort without keys: {:?}", start.elapsed());
+    let unpacked_bids: Vec<u32> = unpacked.iter().map(|h| h.bid).collect();
+
+    let start = Instant::now();
     hands.sort();
-    let mut rank = 1;
-    for h in hands {
-        p2_result += rank * h.bid;
-        rank += 1;
+    println!("sort with keys: {:?}", start.elapsed());
+
+    //sorts are stable, so both must give the same order
+    assert!(hands.iter().map(|h| h.bid).eq(unpacked_bids.into_iter()));
+}
+
+fn main() {
+    let file = "data/day7.txt";
+    let hands: Vec<Hand> = load_hands(file, &Rc::new(RuleSet::p1()));
+    println!("p1: {}", get_winnings(hands));
+
+    let hands: Vec<Hand> = load_hands(file, &Rc::new(RuleSet::p2()));
+    println!("p2: {}", get_winnings(hands));
+
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(|a| a.as_str()) {
+        None => {}
+        //odds for a partly known hand: day7 odds <hand, ? for unknown cards> [copies per card]
+        Some("odds") => {
+            let Some(hand) = args.get(2) else {
+                println!("usage: day7 odds <hand, ? for unknown cards> [copies per card]");
+                return;
+            };
+            for rules in [Rc::new(RuleSet::p1()), Rc::new(RuleSet::p2())] {
+                let deck = match args.get(3) {
+                    Some(copies) => Deck::finite(&rules, copies.parse::<u64>().expect(copies)),
+                    None => Deck::infinite(&rules),
+                };
+                let mut hands: Vec<Hand> = load_hands(file, &rules);
+                println!(
+                    "{} jokers:",
+                    if rules.wild.is_empty() {
+                        "without"
+                    } else {
+                        "with"
+                    }
+                );
+                print_odds(hand, &rules, &deck, &mut hands);
+            }
+        }
+        //sort timing on random hands: day7 bench [hand count]
+        Some("bench") => {
+            let count = args.get(2).map(|c| c.parse::<usize>().expect(c));
+            bench(count.unwrap_or(1_000_000));
+        }
+        //other variants: day7 <rules file> [hands file]
+        Some(rules_file) => {
+            let rules = RuleSet::load(&fs::read_to_string(rules_file).expect("Cannot read rules."));
+            let file = args.get(2).map(|f| f.as_str()).unwrap_or(file);
+            let hands: Vec<Hand> = load_hands(file, &Rc::new(rules));
+            for h in hands.iter() {
+                println!("{}: {}", h.bid, h.get_hand_type_name());
+            }
+            println!("{}: {}", rules_file, get_winnings(hands));
+        }
     }
-    println!("p2: {}", p2_result);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
 
     #[test]
     fn test_card() {
-        let card = Card {
-            label: 'A',
-            p1: true,
-        };
-        assert_eq!(card.get_value(), 14);
-        let card = Card {
-            label: 'K',
-            p1: true,
-        };
-        assert_eq!(card.get_value(), 13);
-        let card = Card {
-            label: 'Q',
-            p1: true,
-        };
-        assert_eq!(card.get_value(), 12);
-        let card = Card {
-            label: 'J',
-            p1: true,
-        };
-        assert_eq!(card.get_value(), 11);
-        let card = Card {
-            label: 'T',
-            p1: true,
-        };
-        assert_eq!(card.get_value(), 10);
-        let card = Card {
-            label: '9',
-            p1: true,
-        };
-        assert_eq!(card.get_value(), 9);
-        let card = Card {
-            label: '1',
-            p1: true,
-        };
-        assert_eq!(card.get_value(), 1);
+        let rules = RuleSet::p1();
+        assert_eq!(rules.get_value('A'), 14);
+        assert_eq!(rules.get_value('K'), 13);
+        assert_eq!(rules.get_value('Q'), 12);
+        assert_eq!(rules.get_value('J'), 11);
+        assert_eq!(rules.get_value('T'), 10);
+        assert_eq!(rules.get_value('9'), 9);
+        assert_eq!(rules.get_value('1'), 1);
+
+        //p2, J is the weakest card
+        let rules = RuleSet::p2();
+        assert_eq!(rules.get_value('J'), 1);
+        assert_eq!(rules.get_value('2'), 2);
+        assert_eq!(rules.get_value('A'), 13);
     }
 
     #[test]
     fn test_hand() {
-        let hand = Hand {
-            cards: vec![
-                Card {
-                    label: 'A',
-                    p1: true,
-                },
-                Card {
-                    label: 'A',
-                    p1: true,
-                },
-                Card {
-                    label: 'A',
-                    p1: true,
-                },
-                Card {
-                    label: 'A',
-                    p1: true,
-                },
-                Card {
-                    label: 'A',
-                    p1: true,
-                },
-            ],
-            bid: 99,
-            p1: true,
-        };
+        let p1 = Rc::new(RuleSet::p1());
+        let p2 = Rc::new(RuleSet::p2());
+        let hand = Hand::new("AAAAA", 99, &p1);
         assert_eq!(hand.get_hand_type(), 7);
-        let hand = Hand {
-            cards: vec![
-                Card {
-                    label: 'A',
-                    p1: true,
-                },
-                Card {
-                    label: 'A',
-                    p1: true,
-                },
-                Card {
-                    label: '8',
-                    p1: true,
-                },
-                Card {
-                    label: 'A',
-                    p1: true,
-                },
-                Card {
-                    label: 'A',
-                    p1: true,
-                },
-            ],
-            bid: 99,
-            p1: true,
-        };
+        let hand = Hand::new("AA8AA", 99, &p1);
         assert_eq!(hand.get_hand_type(), 6);
-        let hand = Hand {
-            cards: vec![
-                Card {
-                    label: '2',
-                    p1: true,
-                },
-                Card {
-                    label: '3',
-                    p1: true,
-                },
-                Card {
-                    label: '3',
-                    p1: true,
-                },
-                Card {
-                    label: '3',
-                    p1: true,
-                },
-                Card {
-                    label: '2',
-                    p1: true,
-                },
-            ],
-            bid: 99,
-            p1: true,
-        };
+        let hand = Hand::new("23332", 99, &p1);
         assert_eq!(hand.get_hand_type(), 5);
-        let hand = Hand {
-            cards: vec![
-                Card {
-                    label: 'T',
-                    p1: true,
-                },
-                Card {
-                    label: 'T',
-                    p1: true,
-                },
-                Card {
-                    label: 'T',
-                    p1: true,
-                },
-                Card {
-                    label: '9',
-                    p1: true,
-                },
-                Card {
-                    label: '8',
-                    p1: true,
-                },
-            ],
-            bid: 99,
-            p1: true,
-        };
+        let hand = Hand::new("TTT98", 99, &p1);
         assert_eq!(hand.get_hand_type(), 4);
-        let hand = Hand {
-            cards: vec![
-                Card {
-                    label: '2',
-                    p1: true,
-                },
-                Card {
-                    label: '3',
-                    p1: true,
-                },
-                Card {
-                    label: '4',
-                    p1: true,
-                },
-                Card {
-                    label: '3',
-                    p1: true,
-                },
-                Card {
-                    label: '2',
-                    p1: true,
-                },
-            ],
-            bid: 99,
-            p1: true,
-        };
+        let hand = Hand::new("23432", 99, &p1);
         assert_eq!(hand.get_hand_type(), 3);
-        let hand = Hand {
-            cards: vec![
-                Card {
-                    label: 'A',
-                    p1: true,
-                },
-                Card {
-                    label: '2',
-                    p1: true,
-                },
-                Card {
-                    label: '3',
-                    p1: true,
-                },
-                Card {
-                    label: 'A',
-                    p1: true,
-                },
-                Card {
-                    label: '4',
-                    p1: true,
-                },
-            ],
-            bid: 99,
-            p1: true,
-        };
+        let hand = Hand::new("A23A4", 99, &p1);
         assert_eq!(hand.get_hand_type(), 2);
-        let hand = Hand {
-            cards: vec![
-                Card {
-                    label: '2',
-                    p1: true,
-                },
-                Card {
-                    label: '3',
-                    p1: true,
-                },
-                Card {
-                    label: '4',
-                    p1: true,
-                },
-                Card {
-                    label: '5',
-                    p1: true,
-                },
-                Card {
-                    label: '6',
-                    p1: true,
-                },
-            ],
-            bid: 99,
-            p1: true,
-        };
+        let hand = Hand::new("23456", 99, &p1);
         assert_eq!(hand.get_hand_type(), 1);
 
         //p2
-        let hand = Hand {
-            cards: vec![
-                Card {
-                    label: 'T',
-                    p1: false,
-                },
-                Card {
-                    label: '5',
-                    p1: false,
-                },
-                Card {
-                    label: '5',
-                    p1: false,
-                },
-                Card {
-                    label: 'J',
-                    p1: false,
-                },
-                Card {
-                    label: '5',
-                    p1: false,
-                },
-            ],
-            bid: 99,
-            p1: false,
-        };
+        let hand = Hand::new("T55J5", 99, &p2);
         assert_eq!(hand.get_hand_type(), 6);
-        let hand = Hand {
-            cards: vec![
-                Card {
-                    label: 'K',
-                    p1: false,
-                },
-                Card {
-                    label: 'T',
-                    p1: false,
-                },
-                Card {
-                    label: 'J',
-                    p1: false,
-                },
-                Card {
-                    label: 'J',
-                    p1: false,
-                },
-                Card {
-                    label: 'T',
-                    p1: false,
-                },
-            ],
-            bid: 99,
-            p1: false,
-        };
+        let hand = Hand::new("KTJJT", 99, &p2);
         assert_eq!(hand.get_hand_type(), 6);
-        let hand = Hand {
-            cards: vec![
-                Card {
-                    label: 'Q',
-                    p1: false,
-                },
-                Card {
-                    label: 'Q',
-                    p1: false,
-                },
-                Card {
-                    label: 'Q',
-                    p1: false,
-                },
-                Card {
-                    label: 'J',
-                    p1: false,
-                },
-                Card {
-                    label: 'A',
-                    p1: false,
-                },
-            ],
-            bid: 99,
-            p1: false,
-        };
+        let hand = Hand::new("QQQJA", 99, &p2);
         assert_eq!(hand.get_hand_type(), 6);
-        let hand = Hand {
-            cards: vec![
-                Card {
-                    label: 'Q',
-                    p1: false,
-                },
-                Card {
-                    label: 'J',
-                    p1: false,
-                },
-                Card {
-                    label: 'J',
-                    p1: false,
-                },
-                Card {
-                    label: 'J',
-                    p1: false,
-                },
-                Card {
-                    label: 'J',
-                    p1: false,
-                },
-            ],
-            bid: 99,
-            p1: false,
-        };
+        let hand = Hand::new("QJJJJ", 99, &p2);
         assert_eq!(hand.get_hand_type(), 7);
     }
 
     #[test]
     fn test_cmp_hands() {
-        let hand1 = Hand {
-            cards: vec![
-                Card {
-                    label: '3',
-                    p1: true,
-                },
-                Card {
-                    label: '3',
-                    p1: true,
-                },
-                Card {
-                    label: '3',
-                    p1: true,
-                },
-                Card {
-                    label: '3',
-                    p1: true,
-                },
-                Card {
-                    label: '2',
-                    p1: true,
-                },
-            ],
-            bid: 99,
-            p1: true,
-        };
+        let p1 = Rc::new(RuleSet::p1());
+        let hand1 = Hand::new("33332", 99, &p1);
 
-        let hand2 = Hand {
-            cards: vec![
-                Card {
-                    label: '2',
-                    p1: true,
-                },
-                Card {
-                    label: 'A',
-                    p1: true,
-                },
-                Card {
-                    label: 'A',
-                    p1: true,
-                },
-                Card {
-                    label: 'A',
-                    p1: true,
-                },
-                Card {
-                    label: 'A',
-                    p1: true,
-                },
-            ],
-            bid: 99,
-            p1: true,
-        };
+        let hand2 = Hand::new("2AAAA", 99, &p1);
 
         assert_eq!(hand1.cmp(&hand2), Ordering::Greater);
-        let hand1 = Hand {
-            cards: vec![
-                Card {
-                    label: '7',
-                    p1: true,
-                },
-                Card {
-                    label: '7',
-                    p1: true,
-                },
-                Card {
-                    label: '7',
-                    p1: true,
-                },
-                Card {
-                    label: '8',
-                    p1: true,
-                },
-                Card {
-                    label: '8',
-                    p1: true,
-                },
-            ],
-            bid: 99,
-            p1: true,
-        };
+        let hand1 = Hand::new("77788", 99, &p1);
 
-        let hand2 = Hand {
-            cards: vec![
-                Card {
-                    label: '7',
-                    p1: true,
-                },
-                Card {
-                    label: '7',
-                    p1: true,
-                },
-                Card {
-                    label: '8',
-                    p1: true,
-                },
-                Card {
-                    label: '8',
-                    p1: true,
-                },
-                Card {
-                    label: '8',
-                    p1: true,
-                },
-            ],
-            bid: 99,
-            p1: true,
-        };
+        let hand2 = Hand::new("77888", 99, &p1);
 
         assert_eq!(hand1.cmp(&hand2), Ordering::Less);
-        let hand1 = Hand {
-            cards: vec![
-                Card {
-                    label: '7',
-                    p1: true,
-                },
-                Card {
-                    label: '7',
-                    p1: true,
-                },
-                Card {
-                    label: '7',
-                    p1: true,
-                },
-                Card {
-                    label: '8',
-                    p1: true,
-                },
-                Card {
-                    label: '8',
-                    p1: true,
-                },
-            ],
-            bid: 99,
-            p1: true,
-        };
+        let hand1 = Hand::new("77788", 99, &p1);
 
-        let hand2 = Hand {
-            cards: vec![
-                Card {
-                    label: '1',
-                    p1: true,
-                },
-                Card {
-                    label: '1',
-                    p1: true,
-                },
-                Card {
-                    label: '1',
-                    p1: true,
-                },
-                Card {
-                    label: '1',
-                    p1: true,
-                },
-                Card {
-                    label: '1',
-                    p1: true,
-                },
-            ],
-            bid: 99,
-            p1: true,
-        };
+        let hand2 = Hand::new("11111", 99, &p1);
 
         assert_eq!(hand1.cmp(&hand2), Ordering::Less);
     }
 
     #[test]
     fn test_cmp_hands_p2() {
-        let hand1 = Hand {
-            cards: vec![
-                Card {
-                    label: 'T',
-                    p1: false,
-                },
-                Card {
-                    label: '5',
-                    p1: false,
-                },
-                Card {
-                    label: '5',
-                    p1: false,
-                },
-                Card {
-                    label: 'J',
-                    p1: false,
-                },
-                Card {
-                    label: '5',
-                    p1: false,
-                },
-            ],
-            bid: 99,
-            p1: false,
-        };
-        let hand2 = Hand {
-            cards: vec![
-                Card {
-                    label: 'K',
-                    p1: false,
-                },
-                Card {
-                    label: 'T',
-                    p1: false,
-                },
-                Card {
-                    label: 'J',
-                    p1: false,
-                },
-                Card {
-                    label: 'J',
-                    p1: false,
-                },
-                Card {
-                    label: 'T',
-                    p1: false,
-                },
-            ],
-            bid: 99,
-            p1: false,
-        };
+        let p2 = Rc::new(RuleSet::p2());
+        let hand1 = Hand::new("T55J5", 99, &p2);
+        let hand2 = Hand::new("KTJJT", 99, &p2);
 
         assert_eq!(hand1.cmp(&hand2), Ordering::Less);
-        let hand1 = Hand {
-            cards: vec![
-                Card {
-                    label: 'J',
-                    p1: false,
-                },
-                Card {
-                    label: 'K',
-                    p1: false,
-                },
-                Card {
-                    label: 'K',
-                    p1: false,
-                },
-                Card {
-                    label: 'K',
-                    p1: false,
-                },
-                Card {
-                    label: '2',
-                    p1: false,
-                },
-            ],
-            bid: 99,
-            p1: false,
-        };
-        let hand2 = Hand {
-            cards: vec![
-                Card {
-                    label: 'Q',
-                    p1: false,
-                },
-                Card {
-                    label: 'Q',
-                    p1: false,
-                },
-                Card {
-                    label: 'Q',
-                    p1: false,
-                },
-                Card {
-                    label: 'Q',
-                    p1: false,
-                },
-                Card {
-                    label: '2',
-                    p1: false,
-                },
-            ],
-            bid: 99,
-            p1: false,
-        };
+        let hand1 = Hand::new("JKKK2", 99, &p2);
+        let hand2 = Hand::new("QQQQ2", 99, &p2);
 
         assert_eq!(hand1.cmp(&hand2), Ordering::Less);
     }
+
+    #[test]
+    fn test_get_winnings() {
+        let example = [
+            ("32T3K", 765),
+            ("T55J5", 684),
+            ("KK677", 28),
+            ("KTJJT", 220),
+            ("QQQJA", 483),
+        ];
+        let p1 = Rc::new(RuleSet::p1());
+        let p2 = Rc::new(RuleSet::p2());
+        let hands = example.iter().map(|h| Hand::new(h.0, h.1, &p1)).collect();
+        assert_eq!(get_winnings(hands), 6440);
+        let hands = example.iter().map(|h| Hand::new(h.0, h.1, &p2)).collect();
+        assert_eq!(get_winnings(hands), 5905);
+    }
+
+    #[test]
+    fn test_load_rules() {
+        //the p2 preset written as a rules file
+        let rules = Rc::new(RuleSet::load(
+            "ranking: J23456789TQKA
+            wild: J
+            size: 5
+            category: high card: 1 1 1 1 1
+            category: one pair: 2 1 1 1
+            category: two pair: 1 2 2
+            category: three of a kind: 3 1 1
+            category: full house: 3 2
+            category: four of a kind: 4 1
+            category: five of a kind: 5",
+        ));
+        assert_eq!(rules.categories[2].groups, vec![2, 2, 1]);
+        assert_eq!(Hand::new("KTJJT", 99, &rules).get_hand_type(), 6);
+        assert_eq!(Hand::new("JJJJJ", 99, &rules).get_hand_type(), 7);
+        assert_eq!(
+            Hand::new("QQQJA", 99, &rules).get_hand_type_name(),
+            "four of a kind"
+        );
+    }
+
+    #[test]
+    fn test_variant_rules() {
+        //three card hands with two wild cards and suits
+        let rules = Rc::new(RuleSet::load(
+            "ranking: 23456789TJQKA
+            wild: 2, 3
+            size: 3
+            suited: true
+            category: high card: *
+            category: pair: 2 1
+            category: flush: * flush
+            category: three of a kind: 3
+            category: suited three of a kind: 3 flush",
+        ));
+        assert_eq!(
+            Hand::new("Ah5c9d", 1, &rules).get_hand_type_name(),
+            "high card"
+        );
+        assert_eq!(Hand::new("AhAc9d", 1, &rules).get_hand_type_name(), "pair");
+        assert_eq!(Hand::new("Ah5h9h", 1, &rules).get_hand_type_name(), "flush");
+        assert_eq!(
+            Hand::new("AhAh2c", 1, &rules).get_hand_type_name(),
+            "suited three of a kind"
+        );
+        assert_eq!(
+            Hand::new("AhAd2c", 1, &rules).get_hand_type_name(),
+            "three of a kind"
+        );
+
+        let a = Hand::new("Ah5h9h", 1, &rules);
+        let b = Hand::new("AhAc9d", 2, &rules);
+        assert_eq!(a.cmp(&b), Ordering::Greater);
+        assert_eq!(rules.wild, vec!['2', '3']);
+    }
+
+    #[test]
+    #[should_panic(expected = "Wild card X is not in the ranking")]
+    fn test_wild_not_ranked() {
+        RuleSet::load("ranking: 23456789TJQKA\nwild: X");
+    }
+
+    fn ratio(n: i64, d: i64) -> BigRational {
+        BigRational::new(BigInt::from(n), BigInt::from(d))
+    }
+
+    #[test]
+    fn test_type_distribution() {
+        let p1 = Rc::new(RuleSet::p1());
+        let deck = Deck::infinite(&p1);
+
+        //nothing unknown
+        let result = get_type_distribution("KK677", &p1, &deck);
+        assert_eq!(result, vec![(3, ratio(1, 1))]);
+
+        //one unknown out of 13 labels: K or 7 give a full house, the rest two pair
+        let result = get_type_distribution("KK77?", &p1, &deck);
+        assert_eq!(result, vec![(3, ratio(11, 13)), (5, ratio(2, 13))]);
+
+        //with jokers J also gives a full house
+        let p2 = Rc::new(RuleSet::p2());
+        let deck = Deck::infinite(&p2);
+        let result = get_type_distribution("KK77?", &p2, &deck);
+        assert_eq!(result, vec![(3, ratio(10, 13)), (5, ratio(3, 13))]);
+
+        let total: BigRational = get_type_distribution("K???J", &p2, &deck)
+            .into_iter()
+            .map(|t| t.1)
+            .sum();
+        assert_eq!(total, ratio(1, 1));
+    }
+
+    #[test]
+    fn test_type_distribution_finite_deck() {
+        let p1 = Rc::new(RuleSet::p1());
+        //two copies of each label, the known cards use up all K and 7
+        let deck = Deck::finite(&p1, 2);
+        let result = get_type_distribution("KK77?", &p1, &deck);
+        assert_eq!(result, vec![(3, ratio(1, 1))]);
+
+        //one K and one Q left, no 7, 10 other labels with 2 cards each
+        let result = get_type_distribution("KQ77?", &p1, &deck);
+        assert_eq!(result, vec![(2, ratio(20, 22)), (3, ratio(2, 22))]);
+    }
+
+    #[test]
+    fn test_expected_rank() {
+        let p1 = Rc::new(RuleSet::p1());
+        let deck = Deck::infinite(&p1);
+        let mut hands: Vec<Hand> = ["32T3K", "T55J5", "KK677", "KTJJT", "QQQJA"]
+            .iter()
+            .map(|h| Hand::new(h, 0, &p1))
+            .collect();
+        hands.sort();
+
+        //a known hand is ranked just like in the sorted list
+        assert_eq!(get_expected_rank("KK678", &p1, &deck, &hands), ratio(2, 1));
+        assert_eq!(get_expected_rank("AAAAA", &p1, &deck, &hands), ratio(6, 1));
+        //KK77? ranks 4 as two pair and 6 as a full house
+        let rank = get_expected_rank("KK77?", &p1, &deck, &hands);
+        assert_eq!(rank, ratio(4 * 11 + 6 * 2, 13));
+    }
+
+    #[test]
+    fn test_sort_key() {
+        let p2 = Rc::new(RuleSet::p2());
+        let hand = Hand::new("KTJJT", 99, &p2);
+        assert_eq!(hand.get_hand_type(), 6);
+        assert_eq!(hand.key, 0x06_0c_0a_01_01_0a);
+
+        //keys give the same order as comparing type and cards one by one
+        let mut rng = StdRng::seed_from_u64(36);
+        for rules in [Rc::new(RuleSet::p1()), p2] {
+            let hands: Vec<Hand> = (0..500)
+                .map(|_| {
+                    let h: String = (0..5)
+                        .map(|_| rules.ranking[rng.gen_range(0..rules.ranking.len())])
+                        .collect();
+                    Hand::new(&h, 0, &rules)
+                })
+                .collect();
+            for a in hands.iter() {
+                for b in hands.iter().take(50) {
+                    assert_eq!(a.cmp(b), a.cmp_unpacked(b));
+                }
+            }
+        }
+    }
 }
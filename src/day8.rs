@@ -1,3 +1,4 @@
+use num::Integer;
 use std::collections::HashMap;
 use std::fs;
 
@@ -9,8 +10,12 @@ fn load_path(file: &str) -> String {
 }
 
 fn load_map(file: &str) -> HashMap<String, (String, String)> {
-    let mut result = HashMap::new();
     let lines = fs::read_to_string(file).expect("file not found");
+    parse_map(&lines)
+}
+
+fn parse_map(lines: &str) -> HashMap<String, (String, String)> {
+    let mut result = HashMap::new();
     for l in lines.split('\n').into_iter().skip(2) {
         if l.len() < 2 {
             continue;
@@ -18,69 +23,203 @@ fn load_map(file: &str) -> HashMap<String, (String, String)> {
 
         let sp: Vec<&str> = l.split('=').collect();
         let label = sp[0].trim().to_string();
-        let left = sp[1].trim()[1 .. 4].to_string();
-        let right = sp[1].trim()[6 .. 9].to_string();
-        result.insert(label, (left, right));
+        //(left, right), names can be any length
+        let pair = sp[1].trim().trim_start_matches('(').trim_end_matches(')');
+        let (left, right) = pair.split_once(',').expect(l);
+        result.insert(label, (left.trim().to_string(), right.trim().to_string()));
 
     }
     result
 }
 
-fn walk(map: &HashMap<String, (String, String)>, current: &String, remaining_steps: &String) -> (String, String) {
-    let is_left = remaining_steps.chars().collect::<Vec<_>>()[0] == 'L';
-    let result:String = if is_left {
-        map.get(current).unwrap().0.clone()
-    } else {
-        map.get(current).unwrap().1.clone()
-    };
-    let steps_after_walk = remaining_steps[1..].to_string();
-    (result, steps_after_walk)
+//the map with node names replaced by indexes, so walking it needs no strings
+struct Network {
+    names: Vec<String>,
+    ids: HashMap<String, usize>,
+    left: Vec<usize>,
+    right: Vec<usize>,
+    path: Vec<bool>, //true for L
+}
+
+impl Network {
+    fn new(map: &HashMap<String, (String, String)>, path: &str) -> Network {
+        let mut names: Vec<String> = map.keys().cloned().collect();
+        names.sort();
+        let ids: HashMap<String, usize> = names
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.clone(), i))
+            .collect();
+        let left = names.iter().map(|n| ids[&map[n].0]).collect();
+        let right = names.iter().map(|n| ids[&map[n].1]).collect();
+        Network {
+            names,
+            ids,
+            left,
+            right,
+            path: path.chars().map(|c| c == 'L').collect(),
+        }
+    }
+
+    fn step(&self, node: usize, path_index: usize) -> usize {
+        if self.path[path_index] {
+            self.left[node]
+        } else {
+            self.right[node]
+        }
+    }
+
+    //steps until a target is reached, None if the walk loops without reaching one
+    fn walk_until(&self, from: usize, is_target: impl Fn(usize) -> bool) -> Option<u64> {
+        //after every (node, path index) pair has been seen the walk can only repeat itself
+        let limit = (self.names.len() * self.path.len()) as u64;
+        let mut cur = from;
+        let mut path_index = 0;
+        let mut steps: u64 = 0;
+        while steps <= limit {
+            cur = self.step(cur, path_index);
+            steps += 1;
+            if is_target(cur) {
+                return Some(steps);
+            }
+            path_index += 1;
+            if path_index == self.path.len() {
+                path_index = 0;
+            }
+        }
+        None
+    }
+
+    fn find_cycle(&self, start: usize, is_target: impl Fn(usize) -> bool) -> GhostCycle {
+        //the walk is fully determined by (node, path index), so it repeats once a pair comes back
+        let mut seen: HashMap<(usize, usize), u64> = HashMap::new();
+        let mut hits = Vec::new();
+        let mut cur = start;
+        let mut steps: u64 = 0;
+        loop {
+            let path_index = (steps % self.path.len() as u64) as usize;
+            if let Some(first) = seen.insert((cur, path_index), steps) {
+                let prefix_len = first;
+                let (prefix_hits, cycle_hits) = hits.into_iter().partition(|h| *h <= prefix_len);
+                return GhostCycle {
+                    start,
+                    prefix_len,
+                    cycle_len: steps - first,
+                    prefix_hits,
+                    cycle_hits,
+                };
+            }
+            cur = self.step(cur, path_index);
+            steps += 1;
+            if is_target(cur) {
+                hits.push(steps);
+            }
+        }
+    }
+}
+
+//where a ghost stands on a target: at the prefix hits once, then at each cycle hit
+//plus any multiple of cycle_len. Cycle hits are within (prefix_len, prefix_len + cycle_len]
+#[derive(Debug)]
+struct GhostCycle {
+    start: usize,
+    prefix_len: u64,
+    cycle_len: u64,
+    prefix_hits: Vec<u64>,
+    cycle_hits: Vec<u64>,
 }
 
-fn run_round(map: &HashMap<String, (String, String)>, path: &String, start: &String) -> (String, u32) {
-    let mut steps: u32 = 0;
-    let mut remaining_steps = path.clone();
-    let mut current: String = start.clone();
-    while remaining_steps.len() > 0 {
-        let walk_result = walk(map, &current, &remaining_steps);
-        steps += 1;
-        if walk_result.0.eq(&String::from("ZZZ")) {
-            return (String::from("ZZZ"), steps);
+impl GhostCycle {
+    fn hits(&self, steps: u64) -> bool {
+        if steps <= self.prefix_len {
+            self.prefix_hits.contains(&steps)
+        } else {
+            let offset = (steps - self.prefix_len) % self.cycle_len;
+            self.cycle_hits
+                .iter()
+                .any(|h| (h - self.prefix_len) % self.cycle_len == offset)
         }
-        remaining_steps = walk_result.1;
-        current = walk_result.0;
     }
-    (current, steps)
+
+    //the ghost is on a target exactly at the multiples of its cycle length
+    fn is_simple(&self) -> bool {
+        self.prefix_hits.is_empty() && self.cycle_hits == vec![self.cycle_len]
+    }
+}
+
+fn lcm_is_valid(cycles: &[GhostCycle]) -> bool {
+    cycles.iter().all(|c| c.is_simple())
+}
+
+//combines t = r1 mod m1 and t = r2 mod m2, None if they can't both hold
+fn combine(r1: i128, m1: i128, r2: i128, m2: i128) -> Option<(i128, i128)> {
+    let e = m1.extended_gcd(&m2);
+    if (r2 - r1) % e.gcd != 0 {
+        return None;
+    }
+    let lcm = m1 / e.gcd * m2;
+    let k = ((r2 - r1) / e.gcd * e.x).rem_euclid(m2 / e.gcd);
+    Some(((r1 + m1 * k).rem_euclid(lcm), lcm))
 }
 
-fn walk_from_to(map: &HashMap<String, (String, String)>, path: &String, from: &String, to: &String, is_p1: bool) -> u32 {
-    let mut p1 = 0;
-    let mut finish = false;
-    let mut cur: String = from.clone();
-    while !finish {
-        let round_result: (String, u32) = run_round(&map, &path, &cur);
-        if is_p1 && round_result.0.eq(to) {
-            finish = true;
-        } else if !is_p1 && round_result.0.chars().collect::<Vec<_>>()[2].eq(&'Z') {
-            finish = true;
+fn first_common_hit(cycles: &[GhostCycle]) -> Option<u128> {
+    //up to the longest prefix, check every hit of the first ghost against the others
+    let last_prefix = cycles.iter().map(|c| c.prefix_len).max()?;
+    let first = &cycles[0];
+    let mut early: Vec<u64> = first.prefix_hits.clone();
+    for h in &first.cycle_hits {
+        early.extend((*h..=last_prefix).step_by(first.cycle_len as usize));
+    }
+    early.sort();
+    if let Some(t) = early.iter().find(|t| cycles.iter().all(|c| c.hits(**t))) {
+        return Some(*t as u128);
+    }
+
+    //past every prefix all ghosts are periodic, so solve the congruences
+    let mut residues: Vec<(i128, i128)> = vec![(0, 1)];
+    for c in cycles {
+        let m = c.cycle_len as i128;
+        let mut next = Vec::new();
+        for (r1, m1) in &residues {
+            for h in &c.cycle_hits {
+                if let Some(r) = combine(*r1, *m1, *h as i128 % m, m) {
+                    if !next.contains(&r) {
+                        next.push(r);
+                    }
+                }
+            }
         }
-        p1 += round_result.1;
-        cur = round_result.0;
+        residues = next;
     }
-    p1
+    let after = last_prefix as i128 + 1;
+    residues
+        .iter()
+        .map(|(r, m)| (r + (after - r + m - 1).div_euclid(*m) * m) as u128)
+        .min()
+}
+
+fn walk_from_to(network: &Network, from: &str, to: &str, is_p1: bool) -> u64 {
+    let from = network.ids[from];
+    let steps = if is_p1 {
+        let to = network.ids[to];
+        network.walk_until(from, |n| n == to)
+    } else {
+        network.walk_until(from, |n| network.names[n].ends_with('Z'))
+    };
+    steps.expect("Target never reached")
 }
 
 fn find_all_a(map: &HashMap<String, (String, String)>) -> Vec<String> {
     map.keys().map(|s| s.clone())
-    .filter(|s| s.chars().collect::<Vec<_>>()[2] == 'A')
+    .filter(|s| s.ends_with('A'))
     .collect::<Vec<_>>()
 }
 
-fn lcm(walks: &HashMap<String, u32>) -> u64 {
-    let nums: Vec<u32> = walks.iter().map(|w| *w.1).collect();
-    let mut lcm: u64 = nums[0] as u64;
+fn lcm(walks: &HashMap<String, u64>) -> u64 {
+    let nums: Vec<u64> = walks.iter().map(|w| *w.1).collect();
+    let mut lcm: u64 = nums[0];
     for n in nums {
-        lcm = num::integer::lcm(lcm, n as u64);
+        lcm = num::integer::lcm(lcm, n);
     }
     lcm
 }
@@ -90,22 +229,43 @@ fn main() {
 
     let map: HashMap<String, (String, String)> = load_map(file);
     let path = load_path(file);
-    let p1 = walk_from_to(&map, &path, &"AAA".to_string(), &"ZZZ".to_string(), true);
+    let network = Network::new(&map, &path);
+    let p1 = walk_from_to(&network, "AAA", "ZZZ", true);
     println!("p1: {}", p1);
-    let mut walks: HashMap<String, u32> = HashMap::new();
+    let mut cycles = Vec::new();
     let all_steps_with_a: Vec<String> = find_all_a(&map);
     for a in all_steps_with_a {
-        let walk_steps = walk_from_to(&map, &path, &a, &"!!!".to_string(), false);
-        walks.insert(a.clone(), walk_steps);
+        let cycle = network.find_cycle(network.ids[&a], |n| network.names[n].ends_with('Z'));
+        println!(
+            "{}: prefix {} hits {:?}, cycle {} hits {:?}",
+            network.names[cycle.start],
+            cycle.prefix_len,
+            cycle.prefix_hits,
+            cycle.cycle_len,
+            cycle.cycle_hits
+        );
+        cycles.push(cycle);
     }
-    let p2 = lcm(&walks);
 
-    println!("p2: {}", p2);
+    if lcm_is_valid(&cycles) {
+        let mut walks: HashMap<String, u64> = HashMap::new();
+        for c in &cycles {
+            walks.insert(network.names[c.start].clone(), c.cycle_len);
+        }
+        println!("p2: {}", lcm(&walks));
+    } else {
+        println!("lcm not valid, searching for a common arrival");
+        match first_common_hit(&cycles) {
+            Some(p2) => println!("p2: {}", p2),
+            None => println!("p2: ghosts never arrive together"),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
 
     #[test]
     fn test_load_map() {
@@ -126,19 +286,175 @@ mod tests {
         assert_eq!(path, String::from("LLR"));
     }
 
+    const EXAMPLE: &str = "LLR
+
+AAA = (BBB, BBB)
+BBB = (AAA, ZZZ)
+ZZZ = (ZZZ, ZZZ)
+";
+
     #[test]
-    fn test_walk() {
-        let file = "data/day8_ex.txt";
+    fn test_network() {
+        let network = Network::new(&parse_map(EXAMPLE), "LLR");
+        let aaa = network.ids["AAA"];
+        let bbb = network.ids["BBB"];
+        let zzz = network.ids["ZZZ"];
+        assert_eq!(network.names[bbb], "BBB");
+        assert_eq!(network.step(aaa, 0), bbb);
+        assert_eq!(network.step(bbb, 1), aaa);
+        assert_eq!(network.step(bbb, 2), zzz);
+        assert_eq!(walk_from_to(&network, "AAA", "ZZZ", true), 6);
 
-        let map: HashMap<String, (String, String)> = load_map(file);
-        let path = "RL";
-        let result = walk(&map, &"AAA".to_string(), &path.to_string());
-        assert_eq!(result.0, "BBB".to_string());
-        assert_eq!(result.1, "L".to_string());
-        let path = "L";
-        let result = walk(&map, &"BBB".to_string(), &path.to_string());
-        assert_eq!(result.0, "AAA".to_string());
-        assert_eq!(result.1, "".to_string());
-        assert_eq!(result.1.len(), 0);
-    }
-}
\ No newline at end of file
+        //ZZZ only leads to itself
+        assert_eq!(network.walk_until(zzz, |n| n == aaa), None);
+    }
+
+    #[test]
+    fn test_walk_from_to_p2() {
+        let data = "LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+22A = (22B, XXX)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)
+XXX = (XXX, XXX)
+";
+        let map = parse_map(data);
+        let network = Network::new(&map, "LR");
+        let mut walks: HashMap<String, u64> = HashMap::new();
+        for a in find_all_a(&map) {
+            walks.insert(a.clone(), walk_from_to(&network, &a, "!!!", false));
+        }
+        assert_eq!(walks["11A"], 2);
+        assert_eq!(walks["22A"], 3);
+        assert_eq!(lcm(&walks), 6);
+    }
+
+    #[test]
+    fn test_large_network() {
+        //a single long chain, walked with a path much shorter than the network
+        let count = 200_000;
+        let name = |i: usize| format!("N{:03}", i);
+        let mut data = String::from("LRRLR\n\n");
+        for i in 0..count {
+            let next = name((i + 1) % count);
+            data.push_str(&format!("{} = ({}, {})\n", name(i), next, next));
+        }
+        let map = parse_map(&data);
+        assert_eq!(map.len(), count);
+        assert_eq!(map[&name(0)], (name(1), name(1)));
+        let network = Network::new(&map, "LRRLR");
+        let target = network.ids[&name(count - 1)];
+        assert_eq!(
+            network.walk_until(network.ids[&name(0)], |n| n == target),
+            Some(count as u64 - 1)
+        );
+    }
+
+    #[test]
+    fn test_find_cycle() {
+        let data = "LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+22A = (22B, XXX)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)
+XXX = (XXX, XXX)
+";
+        let network = Network::new(&parse_map(data), "LR");
+        let is_z = |n: usize| network.names[n].ends_with('Z');
+        let first = network.find_cycle(network.ids["11A"], is_z);
+        assert_eq!((first.prefix_len, first.cycle_len), (1, 2));
+        assert_eq!(first.cycle_hits, vec![2]);
+        assert!(first.is_simple());
+
+        //22Z is reached twice per cycle, so lcm only gets it right by luck
+        let second = network.find_cycle(network.ids["22A"], is_z);
+        assert_eq!((second.prefix_len, second.cycle_len), (1, 6));
+        assert!(second.prefix_hits.is_empty());
+        assert_eq!(second.cycle_hits, vec![3, 6]);
+        assert!(!second.is_simple());
+
+        let cycles = vec![first, second];
+        assert!(!lcm_is_valid(&cycles));
+        assert_eq!(first_common_hit(&cycles), Some(6));
+    }
+
+    #[test]
+    fn test_first_common_hit() {
+        let cycle = |prefix_len, prefix_hits: &[u64], cycle_len, cycle_hits: &[u64]| GhostCycle {
+            start: 0,
+            prefix_len,
+            cycle_len,
+            prefix_hits: prefix_hits.to_vec(),
+            cycle_hits: cycle_hits.to_vec(),
+        };
+
+        //hits at 3, 7, 11 and 5, 11, 17, where lcm(3, 5) would be wrong
+        let cycles = vec![cycle(0, &[], 4, &[3]), cycle(0, &[], 6, &[5])];
+        assert!(!lcm_is_valid(&cycles));
+        assert_eq!(first_common_hit(&cycles), Some(11));
+
+        //the only early common hit is in a prefix
+        let cycles = vec![cycle(5, &[2], 3, &[7]), cycle(0, &[], 2, &[2])];
+        assert_eq!(first_common_hit(&cycles), Some(2));
+        let cycles = vec![cycle(5, &[1], 3, &[7]), cycle(0, &[], 2, &[2])];
+        assert_eq!(first_common_hit(&cycles), Some(10));
+
+        //even and odd steps never meet
+        let cycles = vec![cycle(0, &[], 2, &[2]), cycle(0, &[], 4, &[3])];
+        assert_eq!(first_common_hit(&cycles), None);
+
+        let cycles = vec![cycle(0, &[], 11, &[11]), cycle(0, &[], 13, &[13])];
+        assert!(lcm_is_valid(&cycles));
+        assert_eq!(first_common_hit(&cycles), Some(143));
+    }
+
+    #[test]
+    fn test_first_common_hit_random() {
+        //compare against walking all ghosts together on small random networks
+        let mut rng = StdRng::seed_from_u64(38);
+        for _ in 0..200 {
+            let count = rng.gen_range(2..8);
+            let mut map = HashMap::new();
+            for i in 0..count {
+                let left = format!("{:03}", rng.gen_range(0..count));
+                let right = format!("{:03}", rng.gen_range(0..count));
+                map.insert(format!("{:03}", i), (left, right));
+            }
+            let path: String = (0..rng.gen_range(1..4))
+                .map(|_| if rng.gen_bool(0.5) { 'L' } else { 'R' })
+                .collect();
+            let network = Network::new(&map, &path);
+            let targets: Vec<bool> = (0..count).map(|_| rng.gen_bool(0.3)).collect();
+            let starts: Vec<usize> = (0..rng.gen_range(1..4))
+                .map(|_| rng.gen_range(0..count))
+                .collect();
+            let cycles: Vec<GhostCycle> = starts
+                .iter()
+                .map(|s| network.find_cycle(*s, |n| targets[n]))
+                .collect();
+
+            //a ghost has at most 7 * 3 states, so a common hit comes within 21 + 21^3 steps
+            let mut expected = None;
+            let mut ghosts = starts.clone();
+            for steps in 1..10_000u128 {
+                let path_index = (steps as usize - 1) % path.len();
+                for g in ghosts.iter_mut() {
+                    *g = network.step(*g, path_index);
+                }
+                if ghosts.iter().all(|g| targets[*g]) {
+                    expected = Some(steps);
+                    break;
+                }
+            }
+            assert_eq!(first_common_hit(&cycles), expected);
+        }
+    }
+}
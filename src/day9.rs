@@ -1,67 +1,93 @@
-use std::fs::read_to_string;
+use num::{BigInt, Zero};
+use std::{env, fs::read_to_string};
 
-fn is_all_zero(seq: &Vec<i64>) -> bool {
-    seq.iter().all(|a| *a == 0)
+fn is_all_zero(seq: &[BigInt]) -> bool {
+    seq.iter().all(|a| a.is_zero())
 }
 
-fn find_seq_diff(seq: &Vec<i64>) -> Vec<i64> {
+fn find_seq_diff(seq: &[BigInt]) -> Vec<BigInt> {
     let mut result = Vec::new();
     for i in 1..seq.len() {
-        result.push(seq[i] - seq[i - 1]);
+        result.push(&seq[i] - &seq[i - 1]);
     }
     result
 }
 
-fn derive(seq: &Vec<i64>, p1: bool) -> (Vec<Vec<i64>>, Vec<i64>) {
-    let mut degrees = Vec::new();
-    let mut diffs: Vec<i64> = Vec::new();
-    let mut diff = seq.clone();
-    while !is_all_zero(&diff) {
-        let diff_result = find_seq_diff(&diff);
-        diff = diff_result.clone();
-        if !is_all_zero(&diff_result) {
-            degrees.push(diff_result);
+//f(x) = sum of C(x, k) * coefficients[k], where coefficients[k] is the
+//k-th forward difference at index 0
+#[derive(Debug, PartialEq)]
+struct Newton {
+    coefficients: Vec<BigInt>,
+    len: usize,
+    //false when the samples ran out before a zero row of differences showed up,
+    //then the fit is the lowest degree going through all of them
+    exact: bool,
+}
+
+impl Newton {
+    fn fit(seq: &[i64]) -> Result<Newton, String> {
+        if seq.is_empty() {
+            return Err("Nothing to fit".to_string());
         }
+        let mut coefficients = Vec::new();
+        let mut diff: Vec<BigInt> = seq.iter().map(|v| BigInt::from(*v)).collect();
+        let mut exact = false;
+        while !diff.is_empty() {
+            if is_all_zero(&diff) {
+                exact = true;
+                break;
+            }
+            coefficients.push(diff[0].clone());
+            diff = find_seq_diff(&diff);
+        }
+        Ok(Newton {
+            coefficients,
+            len: seq.len(),
+            exact,
+        })
     }
 
-    //fill 0
-    for _i in 0..degrees.len() {
-        diffs.push(0);
+    fn degree(&self) -> Option<usize> {
+        //None for the zero sequence
+        self.coefficients.len().checked_sub(1)
     }
 
-    let mut prev = 0;
-    for (i, d) in diffs.iter_mut().enumerate().rev() {
-        if i == degrees.len() - 1 {
-            // last one
-            *d = *degrees[i].last().unwrap();
+    fn evaluate(&self, index: i64) -> BigInt {
+        let x = BigInt::from(index);
+        let mut binomial = BigInt::from(1);
+        let mut result = BigInt::zero();
+        for (k, c) in self.coefficients.iter().enumerate() {
+            result += c * &binomial;
+            //C(x, k + 1) = C(x, k) * (x - k) / (k + 1), always exact
+            binomial = binomial * (&x - k) / (k + 1);
+        }
+        result
+    }
+
+    //value `steps` after the last sample, or before the first one when negative
+    fn extrapolate(&self, steps: i64) -> BigInt {
+        if steps >= 0 {
+            self.evaluate(self.len as i64 - 1 + steps)
         } else {
-            //last val of seq + prev diff
-            *d = if p1 {
-                *degrees[i].last().unwrap() + prev
-            } else {
-                degrees[i][0] - prev
-            };
+            self.evaluate(steps)
         }
-        prev = *d;
     }
-    (degrees, diffs)
 }
 
-fn find_next_seq_val(seq: &Vec<i64>, p1: bool) -> i64 {
-    let derivatives = derive(&seq, p1);
-    if p1 {
-        seq.iter().last().unwrap() + derivatives.1[0]
-    } else {
-        seq[0] - derivatives.1[0]
+fn sum_extrapolated(sequences: &[Vec<i64>], steps: i64) -> Result<BigInt, String> {
+    let mut result = BigInt::zero();
+    for s in sequences {
+        result += Newton::fit(s)?.extrapolate(steps);
     }
+    Ok(result)
 }
 
-fn p1(sequences: &Vec<Vec<i64>>) -> i64 {
-    sequences.iter().map(|s| find_next_seq_val(s, true)).sum()
+fn p1(sequences: &[Vec<i64>]) -> Result<BigInt, String> {
+    sum_extrapolated(sequences, 1)
 }
 
-fn p2(sequences: &Vec<Vec<i64>>) -> i64 {
-    sequences.iter().map(|s| find_next_seq_val(s, false)).sum()
+fn p2(sequences: &[Vec<i64>]) -> Result<BigInt, String> {
+    sum_extrapolated(sequences, -1)
 }
 
 fn load_seqs(file: &str) -> Vec<Vec<i64>> {
@@ -90,8 +116,42 @@ fn main() {
     let file = "data/day9.txt";
     let seqs = load_seqs(file);
 
-    println!("p1: {}", p1(&seqs));
-    println!("p2: {}", p2(&seqs));
+    let mut max_degree = None;
+    for (i, s) in seqs.iter().enumerate() {
+        match Newton::fit(s) {
+            Ok(model) => {
+                if !model.exact {
+                    println!(
+                        "line {}: no zero row within {} values, using degree {:?}",
+                        i + 1,
+                        s.len(),
+                        model.degree()
+                    );
+                }
+                max_degree = max_degree.max(model.degree());
+            }
+            Err(e) => println!("line {}: {}", i + 1, e),
+        }
+    }
+    println!("highest degree: {:?}", max_degree);
+
+    match (p1(&seqs), p2(&seqs)) {
+        (Ok(r1), Ok(r2)) => {
+            println!("p1: {}", r1);
+            println!("p2: {}", r2);
+        }
+        (Err(e), _) | (_, Err(e)) => println!("{}", e),
+    }
+
+    //day9 <steps>: extrapolate further, negative steps go backwards
+    let args: Vec<String> = env::args().collect();
+    if args.len() == 2 {
+        let steps = args[1].parse::<i64>().expect(&args[1]);
+        match sum_extrapolated(&seqs, steps) {
+            Ok(r) => println!("{} steps: {}", steps, r),
+            Err(e) => println!("{}", e),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -99,49 +159,91 @@ mod tests {
 
     use super::*;
 
+    fn big(seq: &[i64]) -> Vec<BigInt> {
+        seq.iter().map(|v| BigInt::from(*v)).collect()
+    }
+
     #[test]
     fn test_is_all_zero() {
-        let s = vec![0, 0, 0, 0, 0];
+        let s = big(&[0, 0, 0, 0, 0]);
         assert!(is_all_zero(&s));
-        let s = vec![1, 2, 3, 4, 5];
+        let s = big(&[1, 2, 3, 4, 5]);
         assert!(!is_all_zero(&s));
     }
 
     #[test]
     fn test_find_seq_diff() {
-        let seq = vec![0, 3, 6, 9, 12, 15];
-        assert_eq!(find_seq_diff(&seq), vec![3, 3, 3, 3, 3]);
+        let seq = big(&[0, 3, 6, 9, 12, 15]);
+        assert_eq!(find_seq_diff(&seq), big(&[3, 3, 3, 3, 3]));
     }
 
     #[test]
-    fn test_derive() {
-        let seq = vec![0, 3, 6, 9, 12, 15];
-        let derivatives: (Vec<Vec<i64>>, Vec<i64>) = derive(&seq, true);
-        assert_eq!(derivatives.0.len(), 1);
-        assert_eq!(derivatives.0[0][0], 3);
-        assert_eq!(derivatives.1.len(), 1);
-        assert_eq!(derivatives.1[0], 3);
-
-        let seq = vec![10, 13, 16, 21, 30, 45];
-        let derivatives: (Vec<Vec<i64>>, Vec<i64>) = derive(&seq, true);
-        assert_eq!(derivatives.0.len(), 3);
-        assert_eq!(derivatives.0[0][0], 3);
-        assert_eq!(derivatives.0[1][0], 0);
-        assert_eq!(derivatives.0[2][0], 2);
-        assert_eq!(derivatives.1.len(), 3);
-        assert_eq!(derivatives.1[0], 23);
-        assert_eq!(derivatives.1[1], 8);
-        assert_eq!(derivatives.1[2], 2);
+    fn test_fit() {
+        let model = Newton::fit(&[0, 3, 6, 9, 12, 15]).unwrap();
+        assert_eq!(model.coefficients, big(&[0, 3]));
+        assert_eq!(model.degree(), Some(1));
+
+        let model = Newton::fit(&[10, 13, 16, 21, 30, 45]).unwrap();
+        assert_eq!(model.coefficients, big(&[10, 3, 0, 2]));
+        assert_eq!(model.degree(), Some(3));
+
+        assert!(model.exact);
+
+        assert_eq!(Newton::fit(&[0, 0, 0]).unwrap().degree(), None);
+        assert_eq!(Newton::fit(&[7, 7]).unwrap().degree(), Some(0));
+        assert!(Newton::fit(&[]).is_err());
+
+        //never reaches a zero row, so the fit goes through every sample
+        let model = Newton::fit(&[1, 2, 4, 8, 16]).unwrap();
+        assert!(!model.exact);
+        assert_eq!(model.degree(), Some(4));
+        assert_eq!(model.extrapolate(1), BigInt::from(31));
+        let model = Newton::fit(&[7]).unwrap();
+        assert!(!model.exact);
+        assert_eq!(model.extrapolate(1), BigInt::from(7));
+        let model = Newton::fit(&[1, 2]).unwrap();
+        assert_eq!(model.degree(), Some(1));
+        assert_eq!(model.extrapolate(1), BigInt::from(3));
+        assert_eq!(model.extrapolate(-1), BigInt::from(0));
     }
 
     #[test]
-    fn test_find_next_seq_val() {
-        let seq = vec![0, 3, 6, 9, 12, 15];
-        assert_eq!(find_next_seq_val(&seq, true), 18);
-        let seq = vec![1, 3, 6, 10, 15, 21];
-        assert_eq!(find_next_seq_val(&seq, true), 28);
-        let seq = vec![10, 13, 16, 21, 30, 45];
-        assert_eq!(find_next_seq_val(&seq, true), 68);
+    fn test_extrapolate() {
+        let model = Newton::fit(&[0, 3, 6, 9, 12, 15]).unwrap();
+        assert_eq!(model.extrapolate(1), BigInt::from(18));
+        assert_eq!(model.extrapolate(-1), BigInt::from(-3));
+        let model = Newton::fit(&[1, 3, 6, 10, 15, 21]).unwrap();
+        assert_eq!(model.extrapolate(1), BigInt::from(28));
+        assert_eq!(model.extrapolate(3), BigInt::from(45));
+        assert_eq!(model.extrapolate(-1), BigInt::from(0));
+        assert_eq!(model.extrapolate(-3), BigInt::from(1));
+        let model = Newton::fit(&[10, 13, 16, 21, 30, 45]).unwrap();
+        assert_eq!(model.extrapolate(1), BigInt::from(68));
+        assert_eq!(model.extrapolate(-1), BigInt::from(5));
+        assert_eq!(model.extrapolate(0), BigInt::from(45));
+    }
+
+    #[test]
+    fn test_evaluate() {
+        //x^3 - 2x, sampled from -3
+        let f = |x: i64| x * x * x - 2 * x;
+        let seq: Vec<i64> = (-3..3).map(f).collect();
+        let model = Newton::fit(&seq).unwrap();
+        for x in -50..50 {
+            assert_eq!(model.evaluate(x), BigInt::from(f(x - 3)));
+        }
+
+        //far past i64
+        let model = Newton::fit(&[0, 1, 8, 27, 64]).unwrap();
+        let expected = BigInt::from(i64::MAX).pow(3);
+        assert_eq!(model.evaluate(i64::MAX), expected);
+
+        //the differences of large values overflow i64 on their own
+        let model = Newton::fit(&[i64::MAX, i64::MIN, i64::MAX, i64::MIN, i64::MAX]).unwrap();
+        assert!(!model.exact);
+        assert_eq!(model.evaluate(2), BigInt::from(i64::MAX));
+        let model = Newton::fit(&[i64::MIN, -1, i64::MAX - 1]).unwrap();
+        assert_eq!(model.extrapolate(1), BigInt::from(i64::MAX) * 2 - 1);
     }
 
     #[test]
@@ -151,7 +253,10 @@ mod tests {
             vec![1, 3, 6, 10, 15, 21],
             vec![10, 13, 16, 21, 30, 45],
         ];
-        assert_eq!(p1(&seqs), 114);
+        assert_eq!(p1(&seqs), Ok(BigInt::from(114)));
+        //short lines are fitted through every value
+        assert_eq!(p1(&[vec![1, 2], vec![5]]), Ok(BigInt::from(8)));
+        assert!(p1(&[vec![]]).is_err());
     }
 
     #[test]
@@ -161,7 +266,7 @@ mod tests {
             vec![1, 3, 6, 10, 15, 21],
             vec![10, 13, 16, 21, 30, 45],
         ];
-        assert_eq!(p2(&seqs), 2);
+        assert_eq!(p2(&seqs), Ok(BigInt::from(2)));
     }
 
     #[test]